@@ -0,0 +1,107 @@
+use anyhow::anyhow;
+use geo_types::Point;
+use rstar::{RTree, RTreeObject, AABB};
+
+use crate::{
+    csa::ConnectionScan,
+    timetable::{
+        grid::distance,
+        stop::{Stop, StopId},
+    },
+};
+
+/// Metres in a degree of latitude, near enough
+pub(super) const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// Where a query starts or ends
+#[derive(Debug, Clone)]
+pub enum Place {
+    /// A CRS code or TIPLOC
+    Stop(String),
+    /// Anywhere, walking in a straight line to or from the stops within
+    /// `radius` metres at `walking_speed` metres per second
+    Point {
+        coord: Point,
+        radius: f64,
+        walking_speed: f64,
+    },
+}
+
+/// A stop's location in the spatial index
+pub(super) struct IndexedStop {
+    tiploc: StopId,
+    coord: Point,
+}
+
+impl RTreeObject for IndexedStop {
+    type Envelope = AABB<[f64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_point([self.coord.x(), self.coord.y()])
+    }
+}
+
+/// Spatial index of the stops with coordinates
+pub(super) fn index_stops(stops: &[Stop]) -> RTree<IndexedStop> {
+    let stops = stops
+        .iter()
+        .filter_map(|s| {
+            Some(IndexedStop {
+                tiploc: s.tiploc.clone(),
+                coord: s.coord?,
+            })
+        })
+        .collect();
+
+    RTree::bulk_load(stops)
+}
+
+impl ConnectionScan {
+    /// Stops within `radius` metres of `coord`, and the seconds it takes to
+    /// walk to each
+    fn stops_near(&self, coord: Point, radius: f64, walking_speed: f64) -> Vec<(StopId, u32)> {
+        let dy = radius / METRES_PER_DEGREE;
+        let dx = dy / coord.y().to_radians().cos().max(0.01);
+        let envelope = AABB::from_corners(
+            [coord.x() - dx, coord.y() - dy],
+            [coord.x() + dx, coord.y() + dy],
+        );
+
+        self.stop_index
+            .locate_in_envelope(&envelope)
+            .filter_map(|s| {
+                let metres = distance(coord, s.coord);
+                let walk_time = (metres / walking_speed).ceil() as u32;
+                (metres <= radius).then(|| (s.tiploc.clone(), walk_time))
+            })
+            .collect()
+    }
+
+    /// Stops a query at `place` can start or end at, and the seconds it
+    /// takes to walk between each and `place`
+    pub(super) fn resolve_place(&self, place: &Place) -> anyhow::Result<Vec<(StopId, u32)>> {
+        match place {
+            Place::Stop(id) => Ok(self
+                .resolve_stops(id)?
+                .into_iter()
+                .map(|s| (s, 0))
+                .collect()),
+            Place::Point {
+                coord,
+                radius,
+                walking_speed,
+            } => {
+                if *walking_speed <= 0.0 {
+                    return Err(anyhow!("Walking speed must be positive"));
+                }
+
+                let stops = self.stops_near(*coord, *radius, *walking_speed);
+                if stops.is_empty() {
+                    return Err(anyhow!("No stops within {radius}m"));
+                }
+
+                Ok(stops)
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    csa::{station::StationInfo, ConnectionScan, Epoch, Place, ScanOptions},
+    timetable::{stop::StopId, trip::TripId},
+};
+
+#[derive(Debug, Clone, Copy)]
+struct ReverseLabel {
+    /// Latest time you can set off from this stop and still arrive in time
+    latest_departure: u32,
+    /// Latest time a train can arrive here and still allow onward travel
+    alight_by: u32,
+}
+
+#[derive(Serialize)]
+struct LatestDeparture {
+    #[serde(flatten)]
+    station: StationInfo,
+    latest_departure: NaiveDateTime,
+}
+
+impl ConnectionScan {
+    fn update_latest(
+        labels: &mut HashMap<StopId, ReverseLabel>,
+        stop: &StopId,
+        label: ReverseLabel,
+    ) -> bool {
+        match labels.get_mut(stop) {
+            Some(current) => {
+                let improved = label.latest_departure > current.latest_departure
+                    || label.alight_by > current.alight_by;
+
+                current.latest_departure = current.latest_departure.max(label.latest_departure);
+                current.alight_by = current.alight_by.max(label.alight_by);
+                improved
+            }
+            None => {
+                labels.insert(stop.clone(), label);
+                true
+            }
+        }
+    }
+
+    /// Walks back from `stop` along any transfers leading to it, given that
+    /// travel onwards from `stop` has to start by `time`.
+    fn relax_incoming_transfers(
+        &self,
+        stop: &StopId,
+        epoch: Epoch,
+        time: u32,
+        labels: &mut HashMap<StopId, ReverseLabel>,
+    ) {
+        for transfer in self.get_incoming_transfers(stop) {
+            let Some(time) = time.checked_sub(transfer.min_transfer_time) else {
+                continue;
+            };
+
+            if transfer.usable(epoch.datetime(time), &ScanOptions::default()) {
+                let label = ReverseLabel {
+                    latest_departure: time,
+                    alight_by: time,
+                };
+                Self::update_latest(labels, &transfer.from_stop, label);
+            }
+        }
+    }
+
+    /// Latest departure time from every station that can still reach
+    /// `destination` by `deadline`.
+    ///
+    /// Connections are scanned backwards by arrival time, mirroring
+    /// `departure_isochrone`.
+    pub fn arrival_isochrone(
+        &self,
+        destination: &Place,
+        deadline: NaiveDateTime,
+    ) -> anyhow::Result<String> {
+        let destinations = self.resolve_place(destination)?;
+        self.check_date(deadline.date())?;
+
+        let epoch = Epoch::new(deadline.date());
+        let time = epoch.seconds(deadline);
+
+        let mut trips: HashSet<(&TripId, u32)> = HashSet::new();
+        // Trips that feed into one in `trips`, and the stop passengers would
+        // stay on board at
+        let mut feeding: HashMap<(&TripId, u32), &StopId> = HashMap::new();
+        let mut labels: HashMap<StopId, ReverseLabel> = HashMap::new();
+
+        // Leave time to walk on from each stop
+        let destinations = destinations
+            .into_iter()
+            .filter_map(|(stop, walk_time)| Some((stop, time.checked_sub(walk_time)?)))
+            .collect_vec();
+
+        for (destination, time) in destinations.iter() {
+            labels.insert(
+                destination.clone(),
+                ReverseLabel {
+                    latest_departure: *time,
+                    alight_by: *time,
+                },
+            );
+        }
+        for (destination, time) in destinations.iter() {
+            self.relax_incoming_transfers(destination, epoch, *time, &mut labels);
+        }
+
+        for c in self.reverse_timeline(epoch, time) {
+            let conn = c.connection;
+            let trip_key = (&conn.trip_id, c.day);
+
+            let can_alight = conn.to_activities.set_down
+                && labels
+                    .get(&conn.to_stop)
+                    .is_some_and(|l| c.arrival_time <= l.alight_by);
+
+            let feeds = feeding.get(&trip_key) == Some(&&conn.to_stop);
+
+            if !can_alight && !feeds && !trips.contains(&trip_key) {
+                continue;
+            }
+
+            trips.insert(trip_key);
+            for feeder in self.feeders(&c, epoch) {
+                feeding.entry(feeder).or_insert(&conn.from_stop);
+            }
+
+            if !conn.from_activities.pick_up {
+                continue;
+            }
+
+            let label = ReverseLabel {
+                latest_departure: c.departure_time,
+                alight_by: c
+                    .departure_time
+                    .saturating_sub(self.min_change_time(&conn.from_stop)),
+            };
+
+            if Self::update_latest(&mut labels, &conn.from_stop, label) {
+                self.relax_incoming_transfers(&conn.from_stop, epoch, label.alight_by, &mut labels);
+            }
+        }
+
+        let times: Vec<LatestDeparture> = self
+            .by_station(labels)
+            .into_iter()
+            .filter_map(|(station, labels)| {
+                let latest_departure = labels.iter().map(|l| l.latest_departure).max()?;
+
+                Some(LatestDeparture {
+                    station,
+                    latest_departure: epoch.datetime(latest_departure),
+                })
+            })
+            .collect();
+
+        geojson::ser::to_feature_collection_string(&times).context("Failed to serialize")
+    }
+}
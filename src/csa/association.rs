@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use itertools::Itertools;
+
+use crate::{
+    csa::{timeline::DatedConnection, ConnectionScan, Epoch},
+    timetable::{
+        association::{Association, AssociationCategory},
+        stop::StopId,
+        trip::{TripId, TripType},
+    },
+};
+
+/// A trip that passengers can stay on board of to continue on another trip,
+/// e.g. the front portion of a train that divides
+#[derive(Debug)]
+struct Feeder {
+    trip_id: TripId,
+    /// Service day of the feeder relative to the continuing trip
+    day_offset: i32,
+    /// Whether the continuing trip is the base train, whose service day the
+    /// association's dates refer to
+    continuing_is_base: bool,
+    /// Index into `Associations::schedules`
+    schedule: usize,
+}
+
+/// Passenger associations between trips, indexed by the trip that continues
+/// and where it does so
+pub(super) struct Associations {
+    feeders: HashMap<(TripId, StopId), Vec<Feeder>>,
+    /// Every association between the same two trips at the same location,
+    /// highest STP indicator first
+    schedules: Vec<Vec<Association>>,
+}
+
+impl Associations {
+    pub fn new(associations: Vec<Association>) -> Self {
+        let schedules = associations
+            .into_iter()
+            .into_group_map_by(|a| (a.base_uid.clone(), a.assoc_uid.clone(), a.location.clone()))
+            .into_values()
+            .map(|mut v| {
+                v.sort_by_key(|a| std::cmp::Reverse(a.stp.priority()));
+                v
+            })
+            .collect_vec();
+
+        let mut feeders: HashMap<(TripId, StopId), Vec<Feeder>> = HashMap::new();
+        for (schedule, associations) in schedules.iter().enumerate() {
+            // Permanent or overlay records carry the details, cancellations
+            // only remove them on some dates
+            let Some(a) = associations
+                .iter()
+                .find(|a| a.passenger && a.category.is_some())
+            else {
+                continue;
+            };
+
+            let offset = a.date_indicator.offset();
+            let (continuing, feeder) = match a.category {
+                Some(AssociationCategory::Join) => (
+                    &a.base_uid,
+                    Feeder {
+                        trip_id: a.assoc_uid.clone(),
+                        day_offset: offset,
+                        continuing_is_base: true,
+                        schedule,
+                    },
+                ),
+                _ => (
+                    &a.assoc_uid,
+                    Feeder {
+                        trip_id: a.base_uid.clone(),
+                        day_offset: -offset,
+                        continuing_is_base: false,
+                        schedule,
+                    },
+                ),
+            };
+
+            feeders
+                .entry((continuing.clone(), a.location.clone()))
+                .or_default()
+                .push(feeder);
+        }
+
+        Self { feeders, schedules }
+    }
+
+    /// Whether the association applies on `date`, the base train's service
+    /// day. The highest priority record covering the date wins.
+    fn applies_on(&self, schedule: usize, date: NaiveDate) -> bool {
+        self.schedules[schedule]
+            .iter()
+            .find(|a| a.runs_on(date))
+            .is_some_and(|a| a.stp != TripType::Cancellation && a.passenger)
+    }
+}
+
+impl ConnectionScan {
+    /// Trips, with their service days, that passengers can stay on board of
+    /// to continue on the trip `c` belongs to from the stop `c` departs
+    pub(super) fn feeders<'a>(
+        &'a self,
+        c: &DatedConnection<'a>,
+        epoch: Epoch,
+    ) -> impl Iterator<Item = (&'a TripId, u32)> + 'a {
+        let day = c.day;
+        self.associations
+            .feeders
+            .get(&(c.connection.trip_id.clone(), c.connection.from_stop.clone()))
+            .into_iter()
+            .flatten()
+            .filter_map(move |f| {
+                let feeder_day = u32::try_from(day as i32 + f.day_offset).ok()?;
+                let base_day = if f.continuing_is_base {
+                    day
+                } else {
+                    feeder_day
+                };
+
+                self.associations
+                    .applies_on(f.schedule, epoch.service_date(base_day))
+                    .then_some((&f.trip_id, feeder_day))
+            })
+    }
+
+    /// If the trip `c` belongs to continues from another trip at the stop `c`
+    /// departs, returns the entry for that trip in `trips` so passengers
+    /// already on board can stay seated.
+    pub(super) fn continued_trip<'a>(
+        &'a self,
+        c: &DatedConnection<'a>,
+        epoch: Epoch,
+        trips: &HashMap<(&'a TripId, u32), (u32, usize, u32)>,
+    ) -> Option<(u32, usize, u32)> {
+        self.feeders(c, epoch)
+            .filter_map(|key| {
+                let &(count, enter, enter_day) = trips.get(&key)?;
+
+                // Only passengers who boarded the feeder before it got here
+                let boarded = self.dated(enter, enter_day);
+                (boarded.connection.from_stop != c.connection.from_stop
+                    && boarded.departure_time < c.departure_time)
+                    .then_some((count, enter, enter_day))
+            })
+            .min_by_key(|&(count, _, _)| count)
+    }
+}
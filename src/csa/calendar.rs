@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+
+use crate::timetable::trip::{Trip, TripId, TripType};
+
+/// Every schedule for each train UID, used to work out which one applies on
+/// a given date
+pub(super) struct Calendar {
+    schedules: Vec<Trip>,
+    /// Indices into `schedules`, grouped by UID
+    uids: HashMap<TripId, Vec<usize>>,
+}
+
+impl Calendar {
+    pub fn new(schedules: Vec<Trip>) -> Self {
+        let mut uids: HashMap<TripId, Vec<usize>> = HashMap::new();
+        for (idx, t) in schedules.iter().enumerate() {
+            uids.entry(t.id.clone()).or_default().push(idx);
+        }
+
+        Self { schedules, uids }
+    }
+
+    /// The schedule `trip_id` runs to on `date`. Of the schedules whose dates
+    /// and days run cover `date`, the one with the highest STP priority
+    /// wins, and the train doesn't run if that is a cancellation.
+    fn schedule_on(&self, trip_id: &TripId, date: NaiveDate) -> Option<usize> {
+        self.uids
+            .get(trip_id)?
+            .iter()
+            .copied()
+            .filter(|&idx| self.schedules[idx].runs_on(date))
+            .max_by_key(|&idx| self.schedules[idx].trip_type.priority())
+            .filter(|&idx| self.schedules[idx].trip_type != TripType::Cancellation)
+    }
+
+    /// Whether `schedule`, an index into the schedules the calendar was built
+    /// from, is the one that runs on `date`
+    pub fn runs_on(&self, schedule: usize, date: NaiveDate) -> bool {
+        self.schedule_on(&self.schedules[schedule].id, date) == Some(schedule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::fixture;
+
+    fn calendar() -> Calendar {
+        let cif = fixture::cif("stp.mca");
+        Calendar::new(cif.trips.into_iter().map(|(_, t)| t).collect())
+    }
+
+    /// STP indicator and start date of the schedule that runs on `date`
+    fn schedule(calendar: &Calendar, uid: &str, date: &str) -> Option<(TripType, String)> {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        calendar
+            .schedule_on(&TripId::new(uid.to_owned()), date)
+            .map(|idx| {
+                let t = &calendar.schedules[idx];
+                (t.trip_type, t.start_date.to_string())
+            })
+    }
+
+    #[test]
+    fn permanent_schedule_runs_outside_overlays() {
+        let calendar = calendar();
+
+        assert_eq!(
+            schedule(&calendar, "C00001", "2024-03-01"),
+            Some((TripType::Permanent, "2024-01-01".to_owned()))
+        );
+        assert_eq!(
+            schedule(&calendar, "C00001", "2024-12-31"),
+            Some((TripType::Permanent, "2024-01-01".to_owned()))
+        );
+        assert_eq!(schedule(&calendar, "C00001", "2025-01-01"), None);
+    }
+
+    #[test]
+    fn overlay_applies_only_on_its_days_run() {
+        let calendar = calendar();
+
+        assert_eq!(
+            schedule(&calendar, "C00001", "2024-03-04"),
+            Some((TripType::Overlay, "2024-03-04".to_owned()))
+        );
+        // The first overlay runs weekdays only, so the weekend falls back to
+        // the permanent schedule
+        assert_eq!(
+            schedule(&calendar, "C00001", "2024-03-09"),
+            Some((TripType::Permanent, "2024-01-01".to_owned()))
+        );
+    }
+
+    #[test]
+    fn several_overlays_each_cover_their_own_dates() {
+        let calendar = calendar();
+
+        assert_eq!(
+            schedule(&calendar, "C00001", "2024-03-12"),
+            Some((TripType::Permanent, "2024-01-01".to_owned()))
+        );
+        assert_eq!(
+            schedule(&calendar, "C00001", "2024-03-16"),
+            Some((TripType::Overlay, "2024-03-11".to_owned()))
+        );
+    }
+
+    #[test]
+    fn cancellation_suppresses_only_its_dates() {
+        let calendar = calendar();
+
+        assert_eq!(schedule(&calendar, "C00001", "2024-03-05"), None);
+        assert_eq!(
+            schedule(&calendar, "C00001", "2024-03-06"),
+            Some((TripType::Overlay, "2024-03-04".to_owned()))
+        );
+
+        assert_eq!(
+            schedule(&calendar, "C00002", "2024-06-09"),
+            Some((TripType::New, "2024-06-01".to_owned()))
+        );
+        assert_eq!(schedule(&calendar, "C00002", "2024-06-12"), None);
+        assert_eq!(
+            schedule(&calendar, "C00002", "2024-06-17"),
+            Some((TripType::New, "2024-06-01".to_owned()))
+        );
+    }
+
+    #[test]
+    fn only_the_winning_schedule_runs() {
+        let calendar = calendar();
+        let date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+
+        let running: Vec<TripType> = (0..calendar.schedules.len())
+            .filter(|&idx| calendar.runs_on(idx, date))
+            .map(|idx| calendar.schedules[idx].trip_type)
+            .collect();
+
+        assert_eq!(running, vec![TripType::Overlay]);
+    }
+}
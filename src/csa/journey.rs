@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::Serialize;
+
+use crate::{
+    csa::{is_origin, ConnectionScan, Epoch, JourneyPointer, Place, ScanOptions},
+    timetable::{
+        details::TripDetails,
+        footpath::Mode,
+        location::Activities,
+        stop::StopId,
+        trip::{TripId, TripMode},
+    },
+};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Leg<'a> {
+    Train {
+        trip_id: TripId,
+        /// Trip this one continues as without changing, e.g. after dividing
+        #[serde(skip_serializing_if = "Option::is_none")]
+        continues_as: Option<TripId>,
+        operator: Option<&'a str>,
+        /// UIC code of international services
+        #[serde(skip_serializing_if = "Option::is_none")]
+        uic_code: Option<&'a str>,
+        /// Whether the schedule is subject to performance monitoring
+        applicable_timetable: bool,
+        mode: TripMode,
+        details: &'a TripDetails,
+        from: StopId,
+        from_name: Option<&'a str>,
+        to: StopId,
+        to_name: Option<&'a str>,
+        departure_time: NaiveDateTime,
+        arrival_time: NaiveDateTime,
+        boarding_activities: Activities,
+        alighting_activities: Activities,
+    },
+    Transfer {
+        /// How the transfer is made, missing when changing within a station
+        mode: Option<Mode>,
+        from: StopId,
+        from_name: Option<&'a str>,
+        to: StopId,
+        to_name: Option<&'a str>,
+        departure_time: NaiveDateTime,
+        arrival_time: NaiveDateTime,
+    },
+    /// Walking from the point the journey starts at to the first stop
+    Access {
+        to: StopId,
+        to_name: Option<&'a str>,
+        departure_time: NaiveDateTime,
+        arrival_time: NaiveDateTime,
+    },
+    /// Walking from the last stop to the point the journey ends at
+    Egress {
+        from: StopId,
+        from_name: Option<&'a str>,
+        departure_time: NaiveDateTime,
+        arrival_time: NaiveDateTime,
+    },
+}
+
+impl Leg<'_> {
+    fn departure_time(&self) -> NaiveDateTime {
+        match self {
+            Leg::Train { departure_time, .. } => *departure_time,
+            Leg::Transfer { departure_time, .. } => *departure_time,
+            Leg::Access { departure_time, .. } => *departure_time,
+            Leg::Egress { departure_time, .. } => *departure_time,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Journey<'a> {
+    /// TIPLOC the journey starts from, one of those at the origin station
+    origin: StopId,
+    /// TIPLOC the journey ends at, one of those at the destination station
+    destination: StopId,
+    departure_time: NaiveDateTime,
+    arrival_time: NaiveDateTime,
+    transfers: u32,
+    legs: Vec<Leg<'a>>,
+}
+
+impl ConnectionScan {
+    /// Earliest arriving journey between `origin` and `destination`, leaving
+    /// at `start_time`
+    pub fn journey(
+        &self,
+        origin: &Place,
+        destination: &Place,
+        start_time: NaiveDateTime,
+        options: &ScanOptions,
+    ) -> anyhow::Result<String> {
+        let origins = self.resolve_place(origin)?;
+        let destinations = self.resolve_place(destination)?;
+        self.check_date(start_time.date())?;
+
+        let epoch = Epoch::new(start_time.date());
+        let mut bags = HashMap::new();
+        self.earliest_arrival_scan(
+            &origins,
+            epoch,
+            epoch.seconds(start_time),
+            &destinations,
+            options,
+            &mut bags,
+        );
+
+        let (destination, egress_time, mut label) = destinations
+            .iter()
+            .filter_map(|(d, walk_time)| Some((d, *walk_time, bags.get(d)?.earliest()?)))
+            .min_by_key(|(_, walk_time, l)| (l.arrival_time + walk_time, l.trips))
+            .context("No journey found")?;
+
+        let arrival_time = epoch.datetime(label.arrival_time + egress_time);
+        let transfers = label.transfers();
+
+        let mut legs = vec![];
+        let mut stop = destination;
+
+        if egress_time > 0 {
+            legs.push(Leg::Egress {
+                from: destination.clone(),
+                from_name: self.location_name(destination),
+                departure_time: epoch.datetime(label.arrival_time),
+                arrival_time,
+            });
+        }
+
+        // Walk back through the labels, at each step picking one at the previous
+        // stop that was early enough and took fewer trips
+        while let Some(journey) = &label.journey {
+            let (prev_stop, prev_label) = match journey {
+                JourneyPointer::Trip {
+                    enter,
+                    enter_day,
+                    exit,
+                    exit_day,
+                } => {
+                    let enter = self.dated(*enter, *enter_day);
+                    let exit = self.dated(*exit, *exit_day);
+                    let from_stop = &enter.connection.from_stop;
+                    let service = &self.services[enter.connection.service];
+
+                    legs.push(Leg::Train {
+                        trip_id: enter.connection.trip_id.clone(),
+                        continues_as: Some(exit.connection.trip_id.clone())
+                            .filter(|t| t != &enter.connection.trip_id),
+                        operator: service.operator(),
+                        uic_code: service
+                            .extra
+                            .as_ref()
+                            .map(|e| e.uic_code.as_str())
+                            .filter(|c| !c.is_empty()),
+                        applicable_timetable: service
+                            .extra
+                            .as_ref()
+                            .is_some_and(|e| e.applicable_timetable),
+                        mode: service.mode,
+                        details: &service.details,
+                        from: from_stop.clone(),
+                        from_name: self.location_name(from_stop),
+                        to: exit.connection.to_stop.clone(),
+                        to_name: self.location_name(&exit.connection.to_stop),
+                        departure_time: epoch.datetime(enter.departure_time),
+                        arrival_time: epoch.datetime(exit.arrival_time),
+                        boarding_activities: enter.connection.from_activities,
+                        alighting_activities: exit.connection.to_activities,
+                    });
+
+                    let min_change_time = if is_origin(&origins, from_stop) {
+                        0
+                    } else {
+                        self.min_change_time(from_stop)
+                    };
+                    let board_by = enter.departure_time.saturating_sub(min_change_time);
+
+                    let prev = bags[from_stop]
+                        .iter()
+                        .filter(|l| l.trips < label.trips && l.arrival_time <= board_by)
+                        .min_by_key(|l| l.trips);
+
+                    (from_stop, prev)
+                }
+                JourneyPointer::Transfer {
+                    from_stop,
+                    departure_time,
+                    mode,
+                } => {
+                    legs.push(Leg::Transfer {
+                        mode: *mode,
+                        from: from_stop.clone(),
+                        from_name: self.location_name(from_stop),
+                        to: stop.clone(),
+                        to_name: self.location_name(stop),
+                        departure_time: epoch.datetime(*departure_time),
+                        arrival_time: epoch.datetime(label.arrival_time),
+                    });
+
+                    let prev = bags[from_stop]
+                        .iter()
+                        .filter(|l| {
+                            l.trips <= label.trips
+                                && l.arrival_time <= *departure_time
+                                && !matches!(l.journey, Some(JourneyPointer::Transfer { .. }))
+                        })
+                        .min_by_key(|l| l.trips);
+
+                    (from_stop, prev)
+                }
+            };
+
+            label = prev_label.context("Failed to reconstruct journey")?;
+            stop = prev_stop;
+        }
+
+        let access_time = origins
+            .iter()
+            .find(|(origin, _)| origin == stop)
+            .map_or(0, |&(_, walk_time)| walk_time);
+
+        if access_time > 0 {
+            // Set off just in time for the first leg
+            let arrival_time = legs
+                .last()
+                .map_or(epoch.datetime(label.arrival_time), |l| l.departure_time());
+
+            legs.push(Leg::Access {
+                to: stop.clone(),
+                to_name: self.location_name(stop),
+                departure_time: arrival_time - TimeDelta::seconds(access_time.into()),
+                arrival_time,
+            });
+        }
+
+        legs.reverse();
+
+        let departure_time = legs.first().map_or(start_time, |l| l.departure_time());
+
+        let journey = Journey {
+            origin: stop.clone(),
+            destination: destination.clone(),
+            departure_time,
+            arrival_time,
+            transfers,
+            legs,
+        };
+
+        serde_json::to_string(&journey).context("Failed to serialize")
+    }
+}
@@ -0,0 +1,85 @@
+use crate::timetable::{footpath::Mode, stop::StopId};
+
+/// How a stop was reached during a scan
+#[derive(Debug)]
+pub(super) enum JourneyPointer {
+    /// Rode a trip from connection `enter` to connection `exit`, on the given
+    /// service days. These only differ between trips that continue from one
+    /// to the other, e.g. when a train divides.
+    Trip {
+        enter: usize,
+        enter_day: u32,
+        exit: usize,
+        exit_day: u32,
+    },
+    /// Walked or otherwise transferred from another stop
+    Transfer {
+        from_stop: StopId,
+        departure_time: u32,
+        mode: Option<Mode>,
+    },
+}
+
+#[derive(Debug)]
+pub(super) struct Label {
+    pub arrival_time: u32,
+    /// Number of trips taken to get here
+    pub trips: u32,
+    pub journey: Option<JourneyPointer>,
+}
+
+impl Label {
+    pub fn transfers(&self) -> u32 {
+        self.trips.saturating_sub(1)
+    }
+}
+
+/// Pareto set of labels at a stop, trading off arrival time against the
+/// number of trips taken
+#[derive(Debug, Default)]
+pub(super) struct Bag(Vec<Label>);
+
+impl Bag {
+    /// Adds `label` unless another label is at least as good on both counts,
+    /// dropping any labels it dominates. Returns whether it was added.
+    pub fn insert(&mut self, label: Label) -> bool {
+        let dominated = self
+            .0
+            .iter()
+            .any(|l| l.arrival_time <= label.arrival_time && l.trips <= label.trips);
+
+        if dominated {
+            return false;
+        }
+
+        self.0
+            .retain(|l| l.arrival_time < label.arrival_time || l.trips < label.trips);
+        self.0.push(label);
+
+        true
+    }
+
+    /// Adds every label in `other`, keeping the Pareto set of the two
+    pub fn merge(&mut self, other: Bag) {
+        for label in other.0 {
+            self.insert(label);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Label> {
+        self.0.iter()
+    }
+
+    pub fn earliest(&self) -> Option<&Label> {
+        self.0.iter().min_by_key(|l| (l.arrival_time, l.trips))
+    }
+
+    /// Fewest trips taken by any label that has arrived by `time`
+    pub fn min_trips_by(&self, time: u32) -> Option<u32> {
+        self.0
+            .iter()
+            .filter(|l| l.arrival_time <= time)
+            .map(|l| l.trips)
+            .min()
+    }
+}
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, fmt::Write};
+
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+use rayon::prelude::*;
+
+use crate::{
+    csa::{timeline::Epoch, ConnectionScan, ScanOptions},
+    timetable::stop::StopId,
+};
+
+/// Start of the binary matrix format, with its version
+const MAGIC: &[u8; 4] = b"ODM1";
+
+/// Travel time between stations that can't be reached
+const UNREACHABLE: u32 = u32::MAX;
+
+/// Seconds from every station to every other, leaving at the same time
+pub struct TravelTimeMatrix {
+    /// CRS codes, in the order of the matrix's rows and columns
+    pub stations: Vec<String>,
+    /// Row-major seconds from the row's station to the column's
+    pub times: Vec<u32>,
+}
+
+impl TravelTimeMatrix {
+    /// Header of destination CRS codes, then a row per origin. Unreachable
+    /// destinations are left empty.
+    pub fn to_csv(&self) -> anyhow::Result<String> {
+        let mut csv = String::new();
+        writeln!(csv, "origin,{}", self.stations.join(","))?;
+
+        for (origin, row) in self.stations.iter().zip(self.rows()) {
+            let times = row
+                .iter()
+                .map(|&t| match t {
+                    UNREACHABLE => String::new(),
+                    t => t.to_string(),
+                })
+                .join(",");
+            writeln!(csv, "{origin},{times}")?;
+        }
+
+        Ok(csv)
+    }
+
+    /// `ODM1`, the number of stations as a `u32`, each CRS code prefixed by
+    /// its length as a `u8`, then the matrix as row-major `u32`s, all little
+    /// endian. Unreachable destinations are `u32::MAX`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = 8 + self.stations.iter().map(|s| 1 + s.len()).sum::<usize>();
+        let mut bytes = Vec::with_capacity(header + 4 * self.times.len());
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.stations.len() as u32).to_le_bytes());
+        for station in self.stations.iter() {
+            bytes.push(station.len() as u8);
+            bytes.extend_from_slice(station.as_bytes());
+        }
+        for time in self.times.iter() {
+            bytes.extend_from_slice(&time.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[u32]> {
+        self.times.chunks(self.stations.len().max(1))
+    }
+}
+
+impl ConnectionScan {
+    /// Earliest travel time between every pair of stations, leaving at
+    /// `start_time`. Each origin is scanned separately, in parallel.
+    pub fn od_matrix(
+        &self,
+        start_time: NaiveDateTime,
+        options: &ScanOptions,
+    ) -> anyhow::Result<TravelTimeMatrix> {
+        self.check_date(start_time.date())?;
+
+        let stations = self.stations.by_crs().sorted_unstable().collect_vec();
+        let index: HashMap<&StopId, usize> = stations
+            .iter()
+            .enumerate()
+            .flat_map(|(i, (_, tiplocs))| tiplocs.iter().map(move |t| (t, i)))
+            .collect();
+
+        let epoch = Epoch::new(start_time.date());
+        let start = epoch.seconds(start_time);
+
+        let rows: Vec<Vec<u32>> = stations
+            .par_iter()
+            .enumerate()
+            .map(|(origin, (_, tiplocs))| {
+                let origins = tiplocs.iter().map(|t| (t.clone(), 0)).collect_vec();
+                let mut bags = HashMap::new();
+                self.earliest_arrival_scan(&origins, epoch, start, &[], options, &mut bags);
+
+                let mut row = vec![UNREACHABLE; stations.len()];
+                row[origin] = 0;
+                for (stop, bag) in bags.iter() {
+                    let (Some(&i), Some(label)) = (index.get(stop), bag.earliest()) else {
+                        continue;
+                    };
+                    row[i] = row[i].min(label.arrival_time - start);
+                }
+
+                row
+            })
+            .collect();
+
+        Ok(TravelTimeMatrix {
+            stations: stations
+                .into_iter()
+                .map(|(crs, _)| crs.to_owned())
+                .collect(),
+            times: rows.concat(),
+        })
+    }
+}
@@ -0,0 +1,687 @@
+use crate::timetable::{
+    association::Association,
+    details::{Reservations, SeatingClass, Sleepers, TrainStatus, TripDetails},
+    footpath::{Availability, Footpath, Mode},
+    location::Activities,
+    metadata::TimetableMetadata,
+    stop::{Stop, StopId},
+    tiploc::Tiplocs,
+    trip::{ExtraDetails, Trip, TripId, TripMode},
+};
+use anyhow::{anyhow, Context};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use itertools::Itertools;
+use rstar::RTree;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+mod access;
+mod arrival;
+mod association;
+mod calendar;
+mod journey;
+mod label;
+mod matrix;
+mod polygon;
+mod profile;
+mod raster;
+mod search;
+mod station;
+mod timeline;
+
+pub use access::Place;
+use access::{index_stops, IndexedStop};
+use association::Associations;
+use calendar::Calendar;
+use label::{Bag, JourneyPointer, Label};
+pub use matrix::TravelTimeMatrix;
+pub use raster::NATIONAL_GRID_WKT;
+use station::{StationInfo, Stations};
+use timeline::{Epoch, DAY};
+
+type Bags = HashMap<StopId, Bag>;
+
+/// Whether `stop` is one of the stops a scan started from, where there is no
+/// change to make before boarding
+fn is_origin(origins: &[(StopId, u32)], stop: &StopId) -> bool {
+    origins.iter().any(|(origin, _)| origin == stop)
+}
+
+#[derive(Debug)]
+struct Connection {
+    trip_id: TripId,
+    from_stop: StopId,
+    to_stop: StopId,
+    departure_time: u32,
+    arrival_time: u32,
+    /// Activities where the connection departs
+    from_activities: Activities,
+    /// Activities where the connection arrives
+    to_activities: Activities,
+    /// Index into `ConnectionScan::services`
+    service: usize,
+    /// Index into the calendar's schedules
+    schedule: usize,
+}
+
+/// Attributes of the service a connection belongs to, used for filtering
+#[derive(Debug)]
+struct Service {
+    /// From the schedule's BX record, if it has one
+    extra: Option<ExtraDetails>,
+    mode: TripMode,
+    details: TripDetails,
+}
+
+impl Service {
+    /// ATOC code of the operator
+    fn operator(&self) -> Option<&str> {
+        self.extra.as_ref().map(|e| e.atoc_code.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Transfer {
+    from_stop: StopId,
+    to_stop: StopId,
+    min_transfer_time: u32,
+    /// How the link is travelled, or `None` for changing between TIPLOCs at
+    /// the same station
+    mode: Option<Mode>,
+    availability: Availability,
+}
+
+impl Transfer {
+    /// Whether the transfer can be set off along at `at`
+    fn usable(&self, at: NaiveDateTime, options: &ScanOptions) -> bool {
+        self.availability.covers(at) && options.allows_transfer(self)
+    }
+}
+
+/// Per-query restrictions on which journeys a scan may use
+#[derive(Debug, Default, Clone)]
+pub struct ScanOptions {
+    /// Maximum number of changes between trips
+    pub max_transfers: Option<u32>,
+    /// Only use services run by these operators (ATOC codes)
+    pub operators: Option<HashSet<String>>,
+    /// Never use services run by these operators (ATOC codes)
+    pub exclude_operators: HashSet<String>,
+    /// Only use services of these modes
+    pub modes: Option<HashSet<TripMode>>,
+    /// Never use services of these modes, e.g. to leave out the buses and
+    /// ships from the `.ZTR` file
+    pub exclude_modes: HashSet<TripMode>,
+    /// Never use services with these statuses, e.g. buses or ships
+    pub exclude_statuses: HashSet<TrainStatus>,
+    /// Never use services in these train categories
+    pub exclude_categories: HashSet<String>,
+    /// Never use services with these power types
+    pub exclude_power_types: HashSet<String>,
+    /// Only use services with these classes of seating, e.g. first class as
+    /// well as standard
+    pub seating_classes: Option<HashSet<SeatingClass>>,
+    /// Only use services with these classes of sleeper berths
+    pub sleepers: Option<HashSet<Sleepers>>,
+    /// Never use services with these reservation requirements, e.g. those
+    /// that have to be booked
+    pub exclude_reservations: HashSet<Reservations>,
+    /// Only transfer between stations by these modes, e.g. walking
+    pub transfer_modes: Option<HashSet<Mode>>,
+    /// Never transfer between stations by these modes, e.g. the Tube
+    pub exclude_transfer_modes: HashSet<Mode>,
+}
+
+impl ScanOptions {
+    fn max_trips(&self) -> u32 {
+        self.max_transfers.map_or(u32::MAX, |t| t + 1)
+    }
+
+    /// Changing within a station is always allowed
+    fn allows_transfer(&self, transfer: &Transfer) -> bool {
+        let Some(mode) = transfer.mode else {
+            return true;
+        };
+
+        self.transfer_modes
+            .as_ref()
+            .is_none_or(|modes| modes.contains(&mode))
+            && !self.exclude_transfer_modes.contains(&mode)
+    }
+
+    fn allows(&self, service: &Service) -> bool {
+        let operator = service.operator().unwrap_or_default();
+
+        let included = self
+            .operators
+            .as_ref()
+            .is_none_or(|ops| ops.contains(operator));
+
+        let mode_included = self
+            .modes
+            .as_ref()
+            .is_none_or(|modes| modes.contains(&service.mode));
+
+        let details = &service.details;
+
+        included
+            && mode_included
+            && !self.exclude_operators.contains(operator)
+            && !self.exclude_modes.contains(&service.mode)
+            && !self.exclude_statuses.contains(&details.status)
+            && !self.exclude_categories.contains(&details.category)
+            && !self.exclude_power_types.contains(&details.power_type)
+            && self
+                .seating_classes
+                .as_ref()
+                .is_none_or(|classes| classes.contains(&details.seating_class))
+            && self
+                .sleepers
+                .as_ref()
+                .is_none_or(|sleepers| details.sleepers.is_some_and(|s| sleepers.contains(&s)))
+            && !self.exclude_reservations.contains(&details.reservations)
+    }
+}
+
+#[derive(Serialize)]
+struct ArrivalTime {
+    #[serde(flatten)]
+    station: StationInfo,
+    arrival_time: NaiveDateTime,
+    transfers: u32,
+    options: Vec<ArrivalOption>,
+}
+
+/// One of the Pareto-optimal ways of reaching a stop
+#[derive(Serialize)]
+struct ArrivalOption {
+    arrival_time: NaiveDateTime,
+    transfers: u32,
+}
+
+pub struct ConnectionScan {
+    metadata: TimetableMetadata,
+    stops: HashMap<StopId, Stop>,
+    transfers: HashMap<StopId, Vec<Transfer>>,
+    incoming_transfers: HashMap<StopId, Vec<Transfer>>,
+    connections: Vec<Connection>,
+    services: Vec<Service>,
+    /// Indices into `connections`, ordered by arrival time
+    arrival_order: Vec<usize>,
+    calendar: Calendar,
+    associations: Associations,
+    stations: Stations,
+    /// Stops with coordinates, for finding those near a point
+    stop_index: RTree<IndexedStop>,
+    tiplocs: Tiplocs,
+}
+
+impl ConnectionScan {
+    pub fn new(
+        metadata: TimetableMetadata,
+        trips: Vec<Trip>,
+        stops: Vec<Stop>,
+        pathways: Vec<Footpath>,
+        associations: Vec<Association>,
+        tiplocs: Tiplocs,
+    ) -> Self {
+        let mut connections = vec![];
+        let mut services = vec![];
+        for (schedule, trip) in trips.iter().enumerate() {
+            // One service for the trip's own attributes, then one for each
+            // change en route
+            let first_service = services.len();
+            services.push(Service {
+                extra: trip.extra.clone(),
+                mode: trip.mode,
+                details: trip.details.clone(),
+            });
+            services.extend(trip.changes.iter().map(|c| Service {
+                extra: trip.extra.clone(),
+                mode: trip.mode,
+                details: c.details.clone(),
+            }));
+
+            // Seconds since midnight at the start of the service day, carrying
+            // on past 24h for trips that run over midnight
+            let mut last_time = 0;
+            let mut service_time = |time: NaiveTime| {
+                let mut secs = time.num_seconds_from_midnight();
+                while secs < last_time {
+                    secs += DAY;
+                }
+                last_time = secs;
+                secs
+            };
+
+            for (location_idx, (from, to)) in trip.locations.iter().tuple_windows().enumerate() {
+                let service = first_service
+                    + trip
+                        .changes
+                        .iter()
+                        .filter(|c| c.location_idx <= location_idx)
+                        .count();
+
+                let departure_time = from
+                    .departure_time()
+                    .expect("Should only be an origin or intermediate stop");
+
+                let from_stop = from.id();
+                let to_stop = to.id();
+                let arrival_time = to
+                    .arrival_time()
+                    .expect("Should only be an intermediate or destination stop");
+
+                connections.push(Connection {
+                    trip_id: trip.id.clone(),
+                    from_stop,
+                    to_stop,
+                    departure_time: service_time(departure_time),
+                    arrival_time: service_time(arrival_time),
+                    from_activities: from.activities(),
+                    to_activities: to.activities(),
+                    service,
+                    schedule,
+                });
+            }
+        }
+
+        connections.sort_by_key(|c| c.departure_time);
+
+        let arrival_order = (0..connections.len())
+            .sorted_by_key(|&i| connections[i].arrival_time)
+            .collect();
+
+        let calendar = Calendar::new(trips);
+
+        let stations = Stations::new(&stops);
+        let stop_index = index_stops(&stops);
+
+        // Footpaths join every TIPLOC at one station to every TIPLOC at the
+        // other, and the TIPLOCs within a station are joined to each other
+        // with only the station's minimum change time to make
+        let footpaths = pathways.iter().flat_map(|p| {
+            let from_stops = stations.get(&p.from_crs).unwrap_or_default();
+            let to_stops = stations.get(&p.to_crs).unwrap_or_default();
+
+            from_stops
+                .iter()
+                .cartesian_product(to_stops)
+                .map(|(from_stop, to_stop)| Transfer {
+                    from_stop: from_stop.clone(),
+                    to_stop: to_stop.clone(),
+                    min_transfer_time: p.time,
+                    mode: Some(p.mode),
+                    availability: p.availability,
+                })
+        });
+
+        let within_stations = stations.iter().flat_map(|tiplocs| {
+            tiplocs
+                .iter()
+                .cartesian_product(tiplocs)
+                .filter(|(from_stop, to_stop)| from_stop != to_stop)
+                .map(|(from_stop, to_stop)| Transfer {
+                    from_stop: from_stop.clone(),
+                    to_stop: to_stop.clone(),
+                    min_transfer_time: 0,
+                    mode: None,
+                    availability: Availability::always(),
+                })
+        });
+
+        let transfers = footpaths.chain(within_stations).collect_vec();
+
+        let incoming_transfers = transfers
+            .iter()
+            .cloned()
+            .into_group_map_by(|t| t.to_stop.clone());
+        let transfers = transfers
+            .into_iter()
+            .into_group_map_by(|t| t.from_stop.clone());
+
+        Self {
+            metadata,
+            stops: stops.into_iter().map(|s| (s.tiploc.clone(), s)).collect(),
+            transfers,
+            incoming_transfers,
+            connections,
+            services,
+            arrival_order,
+            calendar,
+            associations: Associations::new(associations),
+            stations,
+            stop_index,
+            tiplocs,
+        }
+    }
+
+    pub fn metadata(&self) -> &TimetableMetadata {
+        &self.metadata
+    }
+
+    /// Checks a query is for a date the timetable covers, as otherwise it
+    /// would find no trips at all
+    fn check_date(&self, date: NaiveDate) -> anyhow::Result<()> {
+        if self.metadata.covers(date) {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "{date} is outside the dates the timetable covers, {} to {}",
+            self.metadata.valid_from,
+            self.metadata.valid_until
+        ))
+    }
+
+    /// Name of the station at `tiploc`, or its TPS description if it isn't
+    /// one, e.g. for junctions
+    fn location_name(&self, tiploc: &StopId) -> Option<&str> {
+        self.stops
+            .get(tiploc)
+            .map(|s| s.name.as_str())
+            .or_else(|| self.tiplocs.get(tiploc).map(|t| t.description.as_str()))
+    }
+
+    fn get_transfers(&self, stop: &StopId) -> impl Iterator<Item = &Transfer> {
+        match self.transfers.get(stop) {
+            Some(transfers) => transfers.iter(),
+            None => [].iter(),
+        }
+    }
+
+    fn get_incoming_transfers(&self, stop: &StopId) -> impl Iterator<Item = &Transfer> {
+        match self.incoming_transfers.get(stop) {
+            Some(transfers) => transfers.iter(),
+            None => [].iter(),
+        }
+    }
+
+    fn min_change_time(&self, stop: &StopId) -> u32 {
+        self.stops.get(stop).map_or(0, |s| s.min_change_time * 60)
+    }
+
+    fn relax_transfers(
+        &self,
+        stop: &StopId,
+        epoch: Epoch,
+        time: u32,
+        trips: u32,
+        options: &ScanOptions,
+        bags: &mut Bags,
+    ) {
+        let departure_time = epoch.datetime(time);
+        let transfers = self
+            .get_transfers(stop)
+            .filter(|t| t.usable(departure_time, options));
+
+        for transfer in transfers {
+            let label = Label {
+                arrival_time: time + transfer.min_transfer_time,
+                trips,
+                journey: Some(JourneyPointer::Transfer {
+                    from_stop: stop.clone(),
+                    departure_time: time,
+                    mode: transfer.mode,
+                }),
+            };
+
+            bags.entry(transfer.to_stop.clone())
+                .or_default()
+                .insert(label);
+        }
+    }
+
+    /// Scan from `origins` starting at `time` seconds after `epoch`, keeping
+    /// the Pareto-optimal labels by arrival time and number of trips at every
+    /// stop, along with how each was reached. Origins and targets come with
+    /// the seconds it takes to walk to or from them.
+    ///
+    /// If there are `targets` the scan stops as soon as no later connection
+    /// can improve the earliest arrival at any of them.
+    fn earliest_arrival_scan(
+        &self,
+        origins: &[(StopId, u32)],
+        epoch: Epoch,
+        time: u32,
+        targets: &[(StopId, u32)],
+        options: &ScanOptions,
+        bags: &mut Bags,
+    ) {
+        let max_trips = options.max_trips();
+
+        // Fewest trips taken before boarding each trip on each service day, and
+        // where and on which service day it was boarded. Trips that continue
+        // from another one inherit its entry.
+        let mut trips: HashMap<(&TripId, u32), (u32, usize, u32)> = HashMap::new();
+
+        for (origin, walk_time) in origins {
+            bags.entry(origin.clone()).or_default().insert(Label {
+                arrival_time: time + walk_time,
+                trips: 0,
+                journey: None,
+            });
+        }
+        for (origin, walk_time) in origins {
+            self.relax_transfers(origin, epoch, time + walk_time, 0, options, bags);
+        }
+
+        for c in self.timeline(epoch, time) {
+            let target_arrival = targets
+                .iter()
+                .filter_map(|(t, walk_time)| {
+                    Some(bags.get(t)?.earliest()?.arrival_time + walk_time)
+                })
+                .min()
+                .unwrap_or(u32::MAX);
+            if target_arrival <= c.departure_time {
+                break;
+            }
+
+            let conn = c.connection;
+            if !options.allows(&self.services[conn.service]) {
+                // Passengers can't stay on through the excluded part, so the
+                // trip has to be boarded again after it
+                trips.remove(&(&conn.trip_id, c.day));
+                continue;
+            }
+
+            let min_change_time = if is_origin(origins, &conn.from_stop) {
+                0
+            } else {
+                self.min_change_time(&conn.from_stop)
+            };
+
+            let boarding = Some(c.departure_time)
+                .filter(|_| conn.from_activities.pick_up)
+                .and_then(|t| t.checked_sub(min_change_time))
+                .and_then(|t| bags.get(&conn.from_stop)?.min_trips_by(t))
+                .map(|t| t + 1)
+                .filter(|&t| t <= max_trips);
+
+            let trip_key = (&conn.trip_id, c.day);
+            if !trips.contains_key(&trip_key) {
+                if let Some(entry) = self.continued_trip(&c, epoch, &trips) {
+                    trips.insert(trip_key, entry);
+                }
+            }
+
+            let (trip_count, enter, enter_day) = match (trips.get(&trip_key), boarding) {
+                (Some(&(count, enter, day)), Some(boarding)) if count <= boarding => {
+                    (count, enter, day)
+                }
+                (Some(&entry), None) => entry,
+                (_, Some(boarding)) => {
+                    trips.insert(trip_key, (boarding, c.idx, c.day));
+                    (boarding, c.idx, c.day)
+                }
+                (None, None) => continue,
+            };
+
+            if !conn.to_activities.set_down {
+                continue;
+            }
+
+            let label = Label {
+                arrival_time: c.arrival_time,
+                trips: trip_count,
+                journey: Some(JourneyPointer::Trip {
+                    enter,
+                    enter_day,
+                    exit: c.idx,
+                    exit_day: c.day,
+                }),
+            };
+
+            if bags.entry(conn.to_stop.clone()).or_default().insert(label) {
+                self.relax_transfers(
+                    &conn.to_stop,
+                    epoch,
+                    c.arrival_time,
+                    trip_count,
+                    options,
+                    bags,
+                );
+            }
+        }
+    }
+
+    /// Pareto-optimal arrivals at every station reachable from `origin`,
+    /// leaving at `start_time`
+    fn station_arrivals(
+        &self,
+        origin: &Place,
+        start_time: NaiveDateTime,
+        options: &ScanOptions,
+    ) -> anyhow::Result<(Epoch, Vec<(StationInfo, Bag)>)> {
+        let origins = self.resolve_place(origin)?;
+        self.check_date(start_time.date())?;
+
+        let epoch = Epoch::new(start_time.date());
+        let mut bags = HashMap::new();
+        self.earliest_arrival_scan(
+            &origins,
+            epoch,
+            epoch.seconds(start_time),
+            &[],
+            options,
+            &mut bags,
+        );
+
+        let arrivals = self
+            .by_station(bags)
+            .into_iter()
+            .map(|(station, bags)| {
+                let mut bag = Bag::default();
+                for b in bags {
+                    bag.merge(b);
+                }
+                (station, bag)
+            })
+            .collect();
+
+        Ok((epoch, arrivals))
+    }
+
+    /// Earliest arrival at every station reachable from `origin`, leaving at
+    /// `start_time`
+    pub fn departure_isochrone(
+        &self,
+        origin: &Place,
+        start_time: NaiveDateTime,
+        options: &ScanOptions,
+    ) -> anyhow::Result<String> {
+        let (epoch, arrivals) = self.station_arrivals(origin, start_time, options)?;
+
+        let times: Vec<ArrivalTime> = arrivals
+            .into_iter()
+            .filter_map(|(station, bag)| {
+                let earliest = bag.earliest()?;
+
+                let options = bag
+                    .iter()
+                    .sorted_by_key(|l| l.trips)
+                    .map(|l| ArrivalOption {
+                        arrival_time: epoch.datetime(l.arrival_time),
+                        transfers: l.transfers(),
+                    })
+                    .collect();
+
+                Some(ArrivalTime {
+                    station,
+                    arrival_time: epoch.datetime(earliest.arrival_time),
+                    transfers: earliest.transfers(),
+                    options,
+                })
+            })
+            .collect();
+
+        geojson::ser::to_feature_collection_string(&times).context("Failed to serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::fixture;
+
+    /// A Paddington to Oxford trip whose category changes to `XX` between
+    /// Reading and Didcot
+    fn connection_scan() -> ConnectionScan {
+        let cif = fixture::cif("en_route_change.mca");
+        let trips = cif.trips.into_iter().map(|(_, t)| t).collect();
+
+        ConnectionScan::new(
+            cif.metadata,
+            trips,
+            vec![],
+            vec![],
+            vec![],
+            Tiplocs::default(),
+        )
+    }
+
+    /// TIPLOCs reached from Paddington leaving at 07:55 on a Monday
+    fn reached(csa: &ConnectionScan, options: &ScanOptions) -> Vec<String> {
+        let start = NaiveDate::from_ymd_opt(2024, 3, 4)
+            .unwrap()
+            .and_hms_opt(7, 55, 0)
+            .unwrap();
+        let epoch = Epoch::new(start.date());
+        let mut bags = HashMap::new();
+        csa.earliest_arrival_scan(
+            &[(StopId::new("PADTON"), 0)],
+            epoch,
+            epoch.seconds(start),
+            &[],
+            options,
+            &mut bags,
+        );
+
+        bags.keys()
+            .map(|s| s.as_str().to_owned())
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn whole_trip_is_used_without_filters() {
+        let csa = connection_scan();
+
+        assert_eq!(
+            reached(&csa, &ScanOptions::default()),
+            vec!["DIDCOTP", "OXFD", "PADTON", "RDNGSTN"]
+        );
+    }
+
+    #[test]
+    fn excluded_part_of_a_trip_cannot_be_ridden_through() {
+        let csa = connection_scan();
+        let options = ScanOptions {
+            exclude_categories: HashSet::from(["XX".to_owned()]),
+            ..Default::default()
+        };
+
+        assert_eq!(reached(&csa, &options), vec!["PADTON", "RDNGSTN"]);
+    }
+}
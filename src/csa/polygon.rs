@@ -0,0 +1,100 @@
+use std::f64::consts::TAU;
+
+use anyhow::{anyhow, Context};
+use chrono::NaiveDateTime;
+use geo::{unary_union, Coord, LineString, MultiPolygon, Point, Polygon};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::csa::{access::METRES_PER_DEGREE, ConnectionScan, Place, ScanOptions};
+
+/// Vertices of the polygon drawn for each circle
+const CIRCLE_POINTS: usize = 32;
+
+/// Everywhere reachable within a number of minutes
+#[derive(Serialize)]
+struct Band {
+    /// Minutes after leaving the origin
+    cutoff: u32,
+    #[serde(serialize_with = "geojson::ser::serialize_geometry")]
+    geometry: MultiPolygon,
+}
+
+/// A circle of `radius` metres around `centre`
+fn circle(centre: Point, radius: f64) -> Polygon {
+    let dy = radius / METRES_PER_DEGREE;
+    let dx = dy / centre.y().to_radians().cos();
+
+    let ring = (0..CIRCLE_POINTS)
+        .map(|i| {
+            let angle = TAU * i as f64 / CIRCLE_POINTS as f64;
+            Coord {
+                x: centre.x() + dx * angle.cos(),
+                y: centre.y() + dy * angle.sin(),
+            }
+        })
+        .collect_vec();
+
+    Polygon::new(LineString::new(ring), vec![])
+}
+
+impl ConnectionScan {
+    /// Areas reachable from `origin` within each of `cutoffs` minutes of
+    /// `start_time`, by travelling to a station and walking on from it at
+    /// `walking_speed` metres per second for the rest of the time. Each band
+    /// is the union of those circles, largest first.
+    pub fn isochrone_polygons(
+        &self,
+        origin: &Place,
+        start_time: NaiveDateTime,
+        options: &ScanOptions,
+        cutoffs: &[u32],
+        walking_speed: f64,
+    ) -> anyhow::Result<String> {
+        if cutoffs.is_empty() {
+            return Err(anyhow!("No cutoffs given"));
+        }
+        if walking_speed <= 0.0 {
+            return Err(anyhow!("Walking speed must be positive"));
+        }
+        if let Some(cutoff) = cutoffs.iter().find(|c| c.checked_mul(60).is_none()) {
+            return Err(anyhow!("Cutoff of {cutoff} minutes is too large"));
+        }
+
+        let (epoch, arrivals) = self.station_arrivals(origin, start_time, options)?;
+        let start = epoch.seconds(start_time);
+
+        // Seconds to reach each station, and the point we started from
+        let mut travel_times = arrivals
+            .iter()
+            .filter_map(|(station, bag)| {
+                Some((station.geometry, bag.earliest()?.arrival_time - start))
+            })
+            .collect_vec();
+        if let Place::Point { coord, .. } = origin {
+            travel_times.push((*coord, 0));
+        }
+
+        let bands: Vec<Band> = cutoffs
+            .iter()
+            .sorted_unstable_by(|a, b| b.cmp(a))
+            .dedup()
+            .map(|&cutoff| {
+                let circles = travel_times
+                    .iter()
+                    .filter_map(|&(coord, travel_time)| {
+                        let remaining = (cutoff * 60).checked_sub(travel_time)?;
+                        (remaining > 0).then(|| circle(coord, remaining as f64 * walking_speed))
+                    })
+                    .collect_vec();
+
+                Band {
+                    cutoff,
+                    geometry: unary_union(&circles),
+                }
+            })
+            .collect();
+
+        geojson::ser::to_feature_collection_string(&bands).context("Failed to serialize")
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+};
+
+use anyhow::{anyhow, Context};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    csa::{
+        station::StationInfo, timeline::DatedConnection, ConnectionScan, Epoch, Place, ScanOptions,
+    },
+    timetable::{stop::StopId, trip::TripId},
+};
+
+#[derive(Serialize, Debug, Clone, Copy)]
+struct ProfileEntry {
+    departure_time: NaiveDateTime,
+    arrival_time: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct StopProfile {
+    #[serde(flatten)]
+    station: StationInfo,
+    profile: Vec<ProfileEntry>,
+}
+
+/// Pareto-optimal (departure from the origin, arrival) pairs at a stop,
+/// ordered by arrival. Leaving later never gets you there sooner, so the
+/// departures are in order too.
+#[derive(Debug, Default)]
+struct Profile(Vec<(u32, u32)>);
+
+impl Profile {
+    /// Latest departure from the origin that arrives here by `time`
+    fn latest_departure_by(&self, time: u32) -> Option<u32> {
+        let end = self
+            .0
+            .partition_point(|&(_, arrival_time)| arrival_time <= time);
+        self.0[..end]
+            .last()
+            .map(|&(departure_time, _)| departure_time)
+    }
+
+    /// Adds the pair unless one that leaves no earlier and arrives no later
+    /// is already there, returning whether it was added
+    fn insert(&mut self, departure_time: u32, arrival_time: u32) -> bool {
+        let dominated = self
+            .0
+            .iter()
+            .any(|&(d, a)| d >= departure_time && a <= arrival_time);
+        if dominated {
+            return false;
+        }
+
+        self.0
+            .retain(|&(d, a)| !(d <= departure_time && a >= arrival_time));
+        let idx = self.0.partition_point(|&(_, a)| a < arrival_time);
+        self.0.insert(idx, (departure_time, arrival_time));
+        true
+    }
+}
+
+impl ConnectionScan {
+    /// Latest time to leave `origins`, given the time it takes to walk to
+    /// each, and still catch `c` where it departs, including walking on from
+    /// an origin to catch a train at a nearby station
+    fn origin_departure(
+        &self,
+        origins: &[(StopId, u32)],
+        epoch: Epoch,
+        c: &DatedConnection,
+    ) -> Option<u32> {
+        let from_stop = &c.connection.from_stop;
+        let direct = origins
+            .iter()
+            .filter(|(origin, _)| origin == from_stop)
+            .filter_map(|(_, walk_time)| c.departure_time.checked_sub(*walk_time));
+
+        let via_transfer = origins.iter().flat_map(|(origin, walk_time)| {
+            self.get_transfers(origin)
+                .filter(|t| &t.to_stop == from_stop)
+                .filter_map(move |t| {
+                    let transfer_time = t.min_transfer_time + self.min_change_time(from_stop);
+                    let transfer_at = c.departure_time.checked_sub(transfer_time)?;
+
+                    t.usable(epoch.datetime(transfer_at), &ScanOptions::default())
+                        .then_some(transfer_at.checked_sub(*walk_time))
+                        .flatten()
+                })
+        });
+
+        direct.chain(via_transfer).max()
+    }
+
+    /// Pareto-optimal (departure, arrival) pairs for every station reachable
+    /// from `origin` when leaving at any time between `start` and `end`.
+    ///
+    /// This is the profile connection scan with the origin fixed rather than
+    /// the target, so it runs in the opposite direction to the usual one:
+    /// connections are scanned once in order of arrival, and each carries the
+    /// latest departure from the origin that can still catch it. That is the
+    /// departure from the origin to catch it directly, to reach its stop in
+    /// time to change, or to already be on board its trip.
+    pub fn departure_profile(
+        &self,
+        origin: &Place,
+        date: NaiveDate,
+        start: NaiveTime,
+        end: NaiveTime,
+    ) -> anyhow::Result<String> {
+        let origins = self.resolve_place(origin)?;
+        self.check_date(date)?;
+
+        if end < start {
+            return Err(anyhow!("End of departure window must not be before start"));
+        }
+
+        let epoch = Epoch::new(date);
+        let start = epoch.seconds(NaiveDateTime::new(date, start));
+        let end = epoch.seconds(NaiveDateTime::new(date, end));
+
+        let options = ScanOptions::default();
+        let mut profiles: HashMap<StopId, Profile> = HashMap::new();
+        // Latest departure from the origin to be on board each trip
+        let mut trips: HashMap<(&TripId, u32), u32> = HashMap::new();
+        // Times at which it is worth leaving the origin
+        let mut departures = BTreeSet::new();
+
+        for c in self.arrival_timeline(epoch, start, end) {
+            let conn = c.connection;
+            let trip_key = (&conn.trip_id, c.day);
+
+            let boarding = if conn.from_activities.pick_up {
+                let from_origin = self
+                    .origin_departure(&origins, epoch, &c)
+                    .filter(|&t| start <= t && t <= end);
+                departures.extend(from_origin);
+
+                let changing = c
+                    .departure_time
+                    .checked_sub(self.min_change_time(&conn.from_stop))
+                    .and_then(|t| profiles.get(&conn.from_stop)?.latest_departure_by(t));
+
+                from_origin.max(changing)
+            } else {
+                None
+            };
+
+            let staying = trips.get(&trip_key).copied().or_else(|| {
+                self.feeders(&c, epoch)
+                    .filter_map(|key| trips.get(&key).copied())
+                    .max()
+            });
+
+            let Some(departure_time) = boarding.max(staying) else {
+                continue;
+            };
+            trips.insert(trip_key, departure_time);
+
+            if !conn.to_activities.set_down
+                || !profiles
+                    .entry(conn.to_stop.clone())
+                    .or_default()
+                    .insert(departure_time, c.arrival_time)
+            {
+                continue;
+            }
+
+            let transfer_at = epoch.datetime(c.arrival_time);
+            for transfer in self.get_transfers(&conn.to_stop) {
+                if transfer.usable(transfer_at, &options) {
+                    profiles
+                        .entry(transfer.to_stop.clone())
+                        .or_default()
+                        .insert(departure_time, c.arrival_time + transfer.min_transfer_time);
+                }
+            }
+        }
+
+        // Stops within walking distance can be reached at any time, so give
+        // them a pair for each time it is worth leaving
+        for &departure_time in departures.iter() {
+            for (origin, walk_time) in origins.iter() {
+                let arrival_time = departure_time + walk_time;
+                if *walk_time > 0 {
+                    profiles
+                        .entry(origin.clone())
+                        .or_default()
+                        .insert(departure_time, arrival_time);
+                }
+
+                let transfer_at = epoch.datetime(arrival_time);
+                for transfer in self.get_transfers(origin) {
+                    if transfer.usable(transfer_at, &options) {
+                        profiles
+                            .entry(transfer.to_stop.clone())
+                            .or_default()
+                            .insert(departure_time, arrival_time + transfer.min_transfer_time);
+                    }
+                }
+            }
+        }
+
+        // Leaving from a stop doesn't get you anywhere sooner
+        let profiles = profiles
+            .into_iter()
+            .filter(|(id, _)| !origins.contains(&(id.clone(), 0)))
+            .map(|(id, profile)| (id, profile.0));
+
+        let profiles: Vec<StopProfile> = self
+            .by_station(profiles)
+            .into_iter()
+            .map(|(station, profiles)| {
+                // Keep the pairs from any of the station's TIPLOCs that no
+                // later departure arrives at least as early as
+                let mut earliest_arrival = u32::MAX;
+                let profile = profiles
+                    .into_iter()
+                    .flatten()
+                    .sorted_unstable_by_key(|&(departure_time, arrival_time)| {
+                        (Reverse(departure_time), arrival_time)
+                    })
+                    .filter(|&(_, arrival_time)| {
+                        let improved = arrival_time < earliest_arrival;
+                        earliest_arrival = earliest_arrival.min(arrival_time);
+                        improved
+                    })
+                    .collect_vec()
+                    .into_iter()
+                    .rev()
+                    .map(|(departure_time, arrival_time)| ProfileEntry {
+                        departure_time: epoch.datetime(departure_time),
+                        arrival_time: epoch.datetime(arrival_time),
+                    })
+                    .collect();
+
+                StopProfile { station, profile }
+            })
+            .collect();
+
+        geojson::ser::to_feature_collection_string(&profiles).context("Failed to serialize")
+    }
+}
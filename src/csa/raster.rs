@@ -0,0 +1,138 @@
+use std::fmt::Write;
+
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use crate::{
+    csa::{ConnectionScan, Place, ScanOptions},
+    timetable::grid::GridReference,
+};
+
+/// Value of cells that can't be reached in time
+const NODATA: i32 = -9999;
+
+/// Most cells a grid may have, so a small cell size can't exhaust memory
+const MAX_CELLS: usize = 50_000_000;
+
+/// The British National Grid, which grid cells are laid out on, as the WKT
+/// that goes in a `.prj` file beside the grid
+pub const NATIONAL_GRID_WKT: &str = r#"PROJCS["OSGB 1936 / British National Grid",GEOGCS["OSGB 1936",DATUM["OSGB_1936",SPHEROID["Airy 1830",6377563.396,299.3249646,AUTHORITY["EPSG","7001"]],TOWGS84[446.448,-125.157,542.06,0.15,0.247,0.842,-20.489],AUTHORITY["EPSG","6277"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4277"]],PROJECTION["Transverse_Mercator"],PARAMETER["latitude_of_origin",49],PARAMETER["central_meridian",-2],PARAMETER["scale_factor",0.9996012717],PARAMETER["false_easting",400000],PARAMETER["false_northing",-100000],UNIT["metre",1,AUTHORITY["EPSG","9001"]],AXIS["Easting",EAST],AXIS["Northing",NORTH],AUTHORITY["EPSG","27700"]]"#;
+
+impl ConnectionScan {
+    /// Minutes from `start_time` to each cell of a grid of `cell_size` metre
+    /// squares on the National Grid, as an ESRI ASCII grid. A cell's time is
+    /// the quickest of travelling to a station and walking on to the cell's
+    /// centre at `walking_speed` metres per second. Cells further than
+    /// `max_minutes` away have no data, and the grid covers only those that
+    /// don't.
+    pub fn isochrone_grid(
+        &self,
+        origin: &Place,
+        start_time: NaiveDateTime,
+        options: &ScanOptions,
+        max_minutes: u32,
+        cell_size: f64,
+        walking_speed: f64,
+    ) -> anyhow::Result<String> {
+        if cell_size <= 0.0 {
+            return Err(anyhow!("Cell size must be positive"));
+        }
+        if walking_speed <= 0.0 {
+            return Err(anyhow!("Walking speed must be positive"));
+        }
+        let max_time = max_minutes
+            .checked_mul(60)
+            .ok_or_else(|| anyhow!("Cutoff of {max_minutes} minutes is too large"))?;
+
+        let (epoch, arrivals) = self.station_arrivals(origin, start_time, options)?;
+        let start = epoch.seconds(start_time);
+
+        // Where each station reached in time is on the grid, the seconds to
+        // reach it and how far there is time to walk from it
+        let reached = arrivals
+            .iter()
+            .filter_map(|(station, bag)| {
+                Some((station.geometry, bag.earliest()?.arrival_time - start))
+            })
+            .chain(match origin {
+                Place::Point { coord, .. } => Some((*coord, 0)),
+                Place::Stop(_) => None,
+            })
+            .filter(|&(_, travel_time)| travel_time < max_time)
+            .map(|(coord, travel_time)| {
+                let reach = (max_time - travel_time) as f64 * walking_speed;
+                (GridReference::from_wgs84(coord), travel_time, reach)
+            })
+            .collect_vec();
+
+        if reached.is_empty() {
+            return Err(anyhow!("Nowhere reachable within {max_minutes} minutes"));
+        }
+
+        // Snap the extent out to whole cells
+        let (min_x, min_y, max_x, max_y) = reached.iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(min_x, min_y, max_x, max_y), (grid, _, reach)| {
+                (
+                    min_x.min(grid.easting - reach),
+                    min_y.min(grid.northing - reach),
+                    max_x.max(grid.easting + reach),
+                    max_y.max(grid.northing + reach),
+                )
+            },
+        );
+        let min_x = (min_x / cell_size).floor() * cell_size;
+        let min_y = (min_y / cell_size).floor() * cell_size;
+        let cols = ((max_x - min_x) / cell_size).ceil().max(1.0) as usize;
+        let rows = ((max_y - min_y) / cell_size).ceil().max(1.0) as usize;
+        if cols.saturating_mul(rows) > MAX_CELLS {
+            return Err(anyhow!(
+                "Grid would have {cols} by {rows} cells, give a larger cell size"
+            ));
+        }
+
+        // Seconds to each cell, with rows from north to south
+        let mut cells = vec![f64::INFINITY; cols * rows];
+        for (grid, travel_time, reach) in reached {
+            let first_col = ((grid.easting - reach - min_x) / cell_size)
+                .floor()
+                .max(0.0) as usize;
+            let last_col = ((grid.easting + reach - min_x) / cell_size) as usize;
+            let first_row = ((grid.northing - reach - min_y) / cell_size)
+                .floor()
+                .max(0.0) as usize;
+            let last_row = ((grid.northing + reach - min_y) / cell_size) as usize;
+
+            for row in first_row..=last_row.min(rows - 1) {
+                let y = min_y + (row as f64 + 0.5) * cell_size;
+                for col in first_col..=last_col.min(cols - 1) {
+                    let x = min_x + (col as f64 + 0.5) * cell_size;
+                    let metres = (x - grid.easting).hypot(y - grid.northing);
+                    if metres > reach {
+                        continue;
+                    }
+
+                    let cell = &mut cells[(rows - 1 - row) * cols + col];
+                    *cell = cell.min(travel_time as f64 + metres / walking_speed);
+                }
+            }
+        }
+
+        let mut asc = format!(
+            "ncols {cols}\nnrows {rows}\nxllcorner {min_x}\nyllcorner {min_y}\ncellsize {cell_size}\nNODATA_value {NODATA}\n"
+        );
+        for row in cells.chunks(cols) {
+            let line = row
+                .iter()
+                .map(|&seconds| match seconds.is_finite() {
+                    true => format!("{:.1}", seconds / 60.0),
+                    false => NODATA.to_string(),
+                })
+                .join(" ");
+            writeln!(asc, "{line}")?;
+        }
+
+        Ok(asc)
+    }
+}
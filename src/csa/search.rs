@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Context};
+use geo_types::Point;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    csa::ConnectionScan,
+    timetable::stop::{Stop, StopId},
+};
+
+/// Most stops a search returns
+const MAX_RESULTS: usize = 20;
+
+#[derive(Serialize)]
+struct StopMatch<'a> {
+    id: &'a StopId,
+    name: &'a str,
+    crs: &'a str,
+    aliases: &'a [String],
+    #[serde(serialize_with = "geojson::ser::serialize_geometry")]
+    geometry: Point,
+}
+
+/// Uppercases `s` and drops punctuation, so `King's X` matches `KINGS X`
+fn normalise(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+        .split_whitespace()
+        .join(" ")
+}
+
+/// How well a name matches the query, lower being better
+fn name_rank(name: &str, query: &str) -> Option<u8> {
+    let name = normalise(name);
+
+    if name == query {
+        return Some(1);
+    }
+
+    if name.starts_with(query) {
+        return Some(2);
+    }
+
+    // Every word of the query starts a word of the name, e.g. `KINGS CR` for
+    // `LONDON KINGS CROSS`
+    let words = name.split(' ').collect_vec();
+    query
+        .split(' ')
+        .all(|q| words.iter().any(|w| w.starts_with(q)))
+        .then_some(3)
+}
+
+/// How well a stop matches the query, lower being better
+fn rank(stop: &Stop, query: &str) -> Option<u8> {
+    let code = query.replace(' ', "");
+    if stop.crs == code || stop.tiploc == StopId::new(&code) {
+        return Some(0);
+    }
+
+    std::iter::once(&stop.name)
+        .chain(stop.aliases.iter())
+        .filter_map(|name| name_rank(name, query))
+        .min()
+}
+
+impl ConnectionScan {
+    /// Stops whose CRS code or TIPLOC is `query`, or whose name or any alias
+    /// matches it, best matches first
+    pub fn search_stops(&self, query: &str) -> anyhow::Result<String> {
+        let query = normalise(query);
+        if query.is_empty() {
+            return Err(anyhow!("Empty search"));
+        }
+
+        let matches: Vec<StopMatch> = self
+            .stops
+            .values()
+            .filter_map(|stop| Some((rank(stop, &query)?, stop)))
+            .sorted_by_key(|&(rank, stop)| (rank, &stop.name, &stop.tiploc))
+            // Stops without coordinates can't be features
+            .filter_map(|(_, stop)| {
+                Some(StopMatch {
+                    id: &stop.tiploc,
+                    name: &stop.name,
+                    crs: &stop.crs,
+                    aliases: &stop.aliases,
+                    geometry: stop.coord?,
+                })
+            })
+            .take(MAX_RESULTS)
+            .collect();
+
+        geojson::ser::to_feature_collection_string(&matches).context("Failed to serialize")
+    }
+}
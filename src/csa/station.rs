@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use geo_types::Point;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    csa::ConnectionScan,
+    timetable::stop::{Stop, StopId},
+};
+
+/// TIPLOCs grouped into stations by their CRS code, as big stations such as
+/// Clapham Junction have several
+#[derive(Debug, Default)]
+pub(super) struct Stations(HashMap<String, Vec<StopId>>);
+
+impl Stations {
+    pub fn new(stops: &[Stop]) -> Self {
+        let groups = stops
+            .iter()
+            .filter(|s| !s.crs.trim().is_empty())
+            .map(|s| (s.crs.trim().to_owned(), s.tiploc.clone()))
+            .into_group_map();
+
+        Self(groups)
+    }
+
+    /// TIPLOCs at the station with CRS code `crs`
+    pub fn get(&self, crs: &str) -> Option<&[StopId]> {
+        self.0.get(crs).map(Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[StopId]> {
+        self.0.values().map(Vec::as_slice)
+    }
+
+    /// CRS codes of every station, with their TIPLOCs
+    pub fn by_crs(&self) -> impl Iterator<Item = (&str, &[StopId])> {
+        self.0
+            .iter()
+            .map(|(crs, tiplocs)| (crs.as_str(), tiplocs.as_slice()))
+    }
+}
+
+/// A station results are reported for, whether a group of TIPLOCs sharing a
+/// CRS code or a single TIPLOC without one. Flattened into each result's
+/// feature.
+#[derive(Serialize)]
+pub(super) struct StationInfo {
+    /// CRS code, or the TIPLOC if there isn't one
+    pub id: String,
+    pub name: String,
+    pub tiplocs: Vec<StopId>,
+    #[serde(serialize_with = "geojson::ser::serialize_geometry")]
+    pub geometry: Point,
+}
+
+impl ConnectionScan {
+    /// TIPLOCs a query for `id` refers to: every TIPLOC at the station if it
+    /// is a CRS code, otherwise the TIPLOC itself
+    pub(super) fn resolve_stops(&self, id: &str) -> anyhow::Result<Vec<StopId>> {
+        let id = id.trim().to_ascii_uppercase();
+
+        if let Some(tiplocs) = self.stations.get(&id) {
+            return Ok(tiplocs.to_vec());
+        }
+
+        let tiploc = StopId::new(&id);
+        if self.stops.contains_key(&tiploc) || self.tiplocs.get(&tiploc).is_some() {
+            return Ok(vec![tiploc]);
+        }
+
+        Err(anyhow!("Invalid stop id"))
+    }
+
+    /// Groups per-TIPLOC results by station. Stations without coordinates are
+    /// left out, as they can't be features.
+    pub(super) fn by_station<T>(
+        &self,
+        results: impl IntoIterator<Item = (StopId, T)>,
+    ) -> Vec<(StationInfo, Vec<T>)> {
+        let mut stations: HashMap<String, (Vec<StopId>, Vec<T>)> = HashMap::new();
+
+        for (id, result) in results {
+            let Some(stop) = self.stops.get(&id) else {
+                continue;
+            };
+
+            let key = match stop.crs.trim() {
+                "" => id.as_str().to_owned(),
+                crs => crs.to_owned(),
+            };
+
+            let (tiplocs, station_results) = stations.entry(key).or_default();
+            tiplocs.push(id);
+            station_results.push(result);
+        }
+
+        stations
+            .into_iter()
+            .filter_map(|(key, (mut tiplocs, results))| {
+                tiplocs.sort_unstable();
+                let stop = tiplocs
+                    .iter()
+                    .filter_map(|id| self.stops.get(id))
+                    .find(|s| s.coord.is_some())?;
+
+                let station = StationInfo {
+                    id: key,
+                    name: stop.name.clone(),
+                    geometry: stop.coord?,
+                    tiplocs,
+                };
+
+                Some((station, results))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use itertools::Itertools;
+
+use crate::csa::{Connection, ConnectionScan};
+
+pub(super) const DAY: u32 = 24 * 3600;
+
+/// Number of service days a scan looks at: the day before the query (for
+/// trips still running after midnight), the day itself and the day after.
+const SERVICE_DAYS: u32 = 3;
+
+/// Longest journey a scan will look for
+const MAX_JOURNEY_DURATION: u32 = DAY;
+
+/// Times during a scan are seconds since midnight at the start of the day
+/// before the query date, so that every service day involved is positive.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Epoch(NaiveDateTime);
+
+impl Epoch {
+    pub fn new(date: NaiveDate) -> Self {
+        let day_before = date.pred_opt().unwrap_or(date);
+        Self(NaiveDateTime::new(day_before, NaiveTime::MIN))
+    }
+
+    pub fn seconds(&self, time: NaiveDateTime) -> u32 {
+        (time - self.0).num_seconds().clamp(0, u32::MAX as i64) as u32
+    }
+
+    pub fn datetime(&self, seconds: u32) -> NaiveDateTime {
+        self.0 + TimeDelta::seconds(seconds as i64)
+    }
+
+    pub fn service_date(&self, day: u32) -> NaiveDate {
+        self.0.date() + Days::new(day as u64)
+    }
+}
+
+/// A connection on a particular service day, with times relative to the epoch
+#[derive(Debug, Clone, Copy)]
+pub(super) struct DatedConnection<'a> {
+    /// Index into `ConnectionScan::connections`
+    pub idx: usize,
+    /// Service day, counted from the epoch
+    pub day: u32,
+    pub connection: &'a Connection,
+    pub departure_time: u32,
+    pub arrival_time: u32,
+}
+
+impl ConnectionScan {
+    pub(super) fn dated(&self, idx: usize, day: u32) -> DatedConnection<'_> {
+        let connection = &self.connections[idx];
+
+        DatedConnection {
+            idx,
+            day,
+            connection,
+            departure_time: connection.departure_time + day * DAY,
+            arrival_time: connection.arrival_time + day * DAY,
+        }
+    }
+
+    /// Connections that run and depart at or after `from`, in order of
+    /// departure, up to the longest journey we look for
+    pub(super) fn timeline(
+        &self,
+        epoch: Epoch,
+        from: u32,
+    ) -> impl Iterator<Item = DatedConnection<'_>> {
+        let until = from.saturating_add(MAX_JOURNEY_DURATION);
+
+        (0..SERVICE_DAYS)
+            .map(move |day| {
+                let offset = day * DAY;
+                let date = epoch.service_date(day);
+                let start = self
+                    .connections
+                    .partition_point(|c| c.departure_time + offset < from);
+
+                (start..self.connections.len())
+                    .map(move |idx| self.dated(idx, day))
+                    .take_while(move |c| c.departure_time <= until)
+                    .filter(move |c| self.calendar.runs_on(c.connection.schedule, date))
+            })
+            .kmerge_by(|a, b| a.departure_time < b.departure_time)
+    }
+
+    /// Connections that run and depart at or after `from`, in order of
+    /// arrival, up to the longest journey we look for after `until`
+    pub(super) fn arrival_timeline(
+        &self,
+        epoch: Epoch,
+        from: u32,
+        until: u32,
+    ) -> impl Iterator<Item = DatedConnection<'_>> {
+        let until = until.saturating_add(MAX_JOURNEY_DURATION);
+
+        (0..SERVICE_DAYS)
+            .map(move |day| {
+                let offset = day * DAY;
+                let date = epoch.service_date(day);
+                let start = self
+                    .arrival_order
+                    .partition_point(|&i| self.connections[i].arrival_time + offset < from);
+
+                self.arrival_order[start..]
+                    .iter()
+                    .map(move |&idx| self.dated(idx, day))
+                    .take_while(move |c| c.arrival_time <= until)
+                    .filter(move |c| c.departure_time >= from)
+                    .filter(move |c| self.calendar.runs_on(c.connection.schedule, date))
+            })
+            .kmerge_by(|a, b| a.arrival_time < b.arrival_time)
+    }
+
+    /// Connections that run and arrive at or before `until`, latest arrival
+    /// first, back to the longest journey we look for
+    pub(super) fn reverse_timeline(
+        &self,
+        epoch: Epoch,
+        until: u32,
+    ) -> impl Iterator<Item = DatedConnection<'_>> {
+        let from = until.saturating_sub(MAX_JOURNEY_DURATION);
+
+        (0..SERVICE_DAYS)
+            .map(move |day| {
+                let offset = day * DAY;
+                let date = epoch.service_date(day);
+                let end = self
+                    .arrival_order
+                    .partition_point(|&i| self.connections[i].arrival_time + offset <= until);
+
+                self.arrival_order[..end]
+                    .iter()
+                    .rev()
+                    .map(move |&idx| self.dated(idx, day))
+                    .take_while(move |c| c.arrival_time >= from)
+                    .filter(move |c| self.calendar.runs_on(c.connection.schedule, date))
+            })
+            .kmerge_by(|a, b| a.arrival_time > b.arrival_time)
+    }
+}
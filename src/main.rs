@@ -1,58 +1,800 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    collections::HashSet,
+    fs,
+    hash::Hash,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Instant,
+};
 
-use actix_web::{error, get, web, App, HttpServer};
+use actix_web::{error, get, post, web, App, HttpResponse, HttpServer};
+use anyhow::{anyhow, Context};
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{Parser, Subcommand, ValueEnum};
+use geo_types::Point;
+use itertools::Itertools;
 
 use serde::Deserialize;
 
 use crate::{
-    csa::ConnectionScan,
-    timetable::{stop::StopId, Timetable},
+    csa::{ConnectionScan, Place, ScanOptions, TravelTimeMatrix, NATIONAL_GRID_WKT},
+    timetable::{
+        parse::{Named, ParseError, ParseMode},
+        Timetable,
+    },
 };
 mod csa;
 mod timetable;
 
+#[derive(Parser)]
+struct Args {
+    /// Full CIF timetable extract, as the zip archive it was published as or
+    /// a directory of its files
+    timetable: PathBuf,
+    /// CIF update files, or directories containing them, to apply in order on
+    /// top of the full extract
+    #[arg(long = "update")]
+    updates: Vec<PathBuf>,
+    /// Skip malformed records with a warning instead of failing to start
+    #[arg(long)]
+    lenient: bool,
+    /// Directory the update endpoint may apply updates from. The endpoint is
+    /// disabled without it.
+    #[arg(long)]
+    updates_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Something to do with the timetable instead of serving queries
+#[derive(Subcommand)]
+enum Command {
+    /// Write an isochrone grid to disk as an ESRI ASCII grid
+    Grid(GridArgs),
+    /// Write the travel times between every pair of stations to disk
+    Matrix(MatrixArgs),
+}
+
+#[derive(clap::Args)]
+struct GridArgs {
+    #[command(flatten)]
+    origin: OriginParams,
+    /// Departure date
+    #[arg(long)]
+    date: NaiveDate,
+    /// Departure time
+    #[arg(long)]
+    time: NaiveTime,
+    /// Minutes after which cells have no data
+    #[arg(long, default_value_t = DEFAULT_CUTOFFS[DEFAULT_CUTOFFS.len() - 1])]
+    max_minutes: u32,
+    /// Width of each cell in metres
+    #[arg(long, default_value_t = DEFAULT_CELL_SIZE)]
+    cell_size: f64,
+    #[command(flatten)]
+    walk: WalkParams,
+    /// `.asc` file to write. Its projection is written to a `.prj` file
+    /// beside it.
+    output: PathBuf,
+}
+
+impl GridArgs {
+    fn write(&self, csa: &ConnectionScan) -> io::Result<()> {
+        let origin = self
+            .origin
+            .place(&self.walk)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        let grid = csa
+            .isochrone_grid(
+                &origin,
+                NaiveDateTime::new(self.date, self.time),
+                &ScanOptions::default(),
+                self.max_minutes,
+                self.cell_size,
+                self.walk.walking_speed,
+            )
+            .map_err(io::Error::other)?;
+
+        fs::write(&self.output, grid)?;
+        fs::write(self.output.with_extension("prj"), NATIONAL_GRID_WKT)?;
+        println!("Wrote {}", self.output.display());
+
+        Ok(())
+    }
+}
+
+/// How to write a travel time matrix
+#[derive(Deserialize, ValueEnum, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum MatrixFormat {
+    /// A row of seconds per origin, with a column per destination
+    #[default]
+    Csv,
+    /// A station index, then the matrix as little endian `u32` seconds
+    Binary,
+}
+
+impl MatrixFormat {
+    fn write(self, matrix: &TravelTimeMatrix) -> anyhow::Result<Vec<u8>> {
+        match self {
+            MatrixFormat::Csv => Ok(matrix.to_csv()?.into_bytes()),
+            MatrixFormat::Binary => Ok(matrix.to_bytes()),
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            MatrixFormat::Csv => "text/csv",
+            MatrixFormat::Binary => "application/octet-stream",
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct MatrixArgs {
+    /// Departure date
+    #[arg(long)]
+    date: NaiveDate,
+    /// Departure time
+    #[arg(long)]
+    time: NaiveTime,
+    /// Maximum number of changes
+    #[arg(long)]
+    max_transfers: Option<u32>,
+    #[arg(long, value_enum, default_value_t)]
+    format: MatrixFormat,
+    /// File to write
+    output: PathBuf,
+}
+
+impl MatrixArgs {
+    fn write(&self, csa: &ConnectionScan) -> io::Result<()> {
+        let options = ScanOptions {
+            max_transfers: self.max_transfers,
+            ..Default::default()
+        };
+
+        let now = Instant::now();
+        let bytes = csa
+            .od_matrix(NaiveDateTime::new(self.date, self.time), &options)
+            .and_then(|matrix| self.format.write(&matrix))
+            .map_err(io::Error::other)?;
+
+        fs::write(&self.output, bytes)?;
+        println!("Wrote {} in {:?}", self.output.display(), now.elapsed());
+
+        Ok(())
+    }
+}
+
+/// Query parameters restricting which journeys may be used
+#[derive(Deserialize)]
+struct FilterParams {
+    /// Maximum number of changes
+    max_transfers: Option<u32>,
+    /// Comma separated ATOC codes of the only operators to use
+    operators: Option<String>,
+    /// Comma separated ATOC codes of operators to avoid
+    exclude_operators: Option<String>,
+    /// Comma separated modes to use, out of `train`, `bus` and `ship`
+    modes: Option<String>,
+    /// Comma separated modes to avoid
+    exclude_modes: Option<String>,
+    /// Comma separated CIF train status codes to avoid, e.g. `B` for buses
+    exclude_statuses: Option<String>,
+    /// Comma separated CIF train categories to avoid
+    exclude_categories: Option<String>,
+    /// Comma separated CIF power types to avoid
+    exclude_power_types: Option<String>,
+    /// Comma separated classes of seating to use, e.g. `first_and_standard`
+    seating_classes: Option<String>,
+    /// Comma separated classes of sleeper berths to use, e.g. `first_only`
+    sleepers: Option<String>,
+    /// Comma separated reservation requirements to avoid, e.g. `compulsory`
+    exclude_reservations: Option<String>,
+    /// Comma separated modes to transfer between stations by, e.g. `walk`
+    transfer_modes: Option<String>,
+    /// Comma separated modes not to transfer between stations by, e.g. `tube`
+    exclude_transfer_modes: Option<String>,
+}
+
+/// Values of a comma separated query parameter
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn parse_list(list: &Option<String>) -> Option<HashSet<String>> {
+    list.as_ref()
+        .map(|l| split_list(l).map(str::to_ascii_uppercase).collect())
+}
+
+/// Parses every value in `list`, rejecting the query with the accepted
+/// values if any of them isn't one
+fn parse_values<T: Named + Eq + Hash>(list: &str) -> actix_web::Result<HashSet<T>> {
+    split_list(list)
+        .map(|value| {
+            T::parse(value).ok_or_else(|| {
+                error::ErrorBadRequest(format!(
+                    "Invalid value '{value}', expected one of {}",
+                    T::ALL.iter().map(|v| v.name()).join(", ")
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Parses the values of a query parameter listing which to use, if it is
+/// given
+fn parse_only<T: Named + Eq + Hash>(
+    list: &Option<String>,
+) -> actix_web::Result<Option<HashSet<T>>> {
+    list.as_deref().map(parse_values).transpose()
+}
+
+/// Parses the values of a query parameter listing which to avoid
+fn parse_excluded<T: Named + Eq + Hash>(list: &Option<String>) -> actix_web::Result<HashSet<T>> {
+    parse_values(list.as_deref().unwrap_or_default())
+}
+
+impl FilterParams {
+    fn options(&self) -> actix_web::Result<ScanOptions> {
+        Ok(ScanOptions {
+            max_transfers: self.max_transfers,
+            operators: parse_list(&self.operators),
+            exclude_operators: parse_list(&self.exclude_operators).unwrap_or_default(),
+            modes: parse_only(&self.modes)?,
+            exclude_modes: parse_excluded(&self.exclude_modes)?,
+            exclude_statuses: parse_excluded(&self.exclude_statuses)?,
+            exclude_categories: parse_list(&self.exclude_categories).unwrap_or_default(),
+            exclude_power_types: parse_list(&self.exclude_power_types).unwrap_or_default(),
+            seating_classes: parse_only(&self.seating_classes)?,
+            sleepers: parse_only(&self.sleepers)?,
+            exclude_reservations: parse_excluded(&self.exclude_reservations)?,
+            transfer_modes: parse_only(&self.transfer_modes)?,
+            exclude_transfer_modes: parse_excluded(&self.exclude_transfer_modes)?,
+        })
+    }
+}
+
+/// Metres to walk to or from stops when a query is for a point
+const DEFAULT_RADIUS: f64 = 1000.0;
+/// Metres per second, about 5 km/h
+const DEFAULT_WALKING_SPEED: f64 = 1.4;
+
+fn default_radius() -> f64 {
+    DEFAULT_RADIUS
+}
+
+fn default_walking_speed() -> f64 {
+    DEFAULT_WALKING_SPEED
+}
+
+/// Query parameters for walking between a point and the stops near it
+#[derive(Deserialize, clap::Args)]
+struct WalkParams {
+    /// Furthest to walk to or from a stop, in metres
+    #[arg(long, default_value_t = DEFAULT_RADIUS)]
+    #[serde(default = "default_radius")]
+    radius: f64,
+    /// Walking speed in metres per second
+    #[arg(long, default_value_t = DEFAULT_WALKING_SPEED)]
+    #[serde(default = "default_walking_speed")]
+    walking_speed: f64,
+}
+
+impl WalkParams {
+    /// Either the stop `id` or the point at `lat`, `lon`
+    fn place(
+        &self,
+        id: &Option<String>,
+        lat: Option<f64>,
+        lon: Option<f64>,
+    ) -> actix_web::Result<Place> {
+        match (id, lat, lon) {
+            (Some(id), None, None) => Ok(Place::Stop(id.clone())),
+            (None, Some(lat), Some(lon)) => Ok(Place::Point {
+                coord: Point::new(lon, lat),
+                radius: self.radius,
+                walking_speed: self.walking_speed,
+            }),
+            _ => Err(error::ErrorBadRequest(
+                "Give either a stop or a latitude and longitude",
+            )),
+        }
+    }
+}
+
+/// Query parameters for where to set off from. Journeys give the point as
+/// `origin_lat` and `origin_lon` to tell it apart from the destination.
+#[derive(Deserialize, clap::Args)]
+struct OriginParams {
+    /// CRS code or TIPLOC of origin station
+    #[arg(long)]
+    origin: Option<String>,
+    /// Latitude to start from instead of a station
+    #[arg(long, allow_hyphen_values = true)]
+    #[serde(alias = "origin_lat")]
+    lat: Option<f64>,
+    /// Longitude to start from instead of a station
+    #[arg(long, allow_hyphen_values = true)]
+    #[serde(alias = "origin_lon")]
+    lon: Option<f64>,
+}
+
+impl OriginParams {
+    fn place(&self, walk: &WalkParams) -> actix_web::Result<Place> {
+        walk.place(&self.origin, self.lat, self.lon)
+    }
+}
+
+/// Query parameters for where to arrive. Journeys give the point as
+/// `destination_lat` and `destination_lon` to tell it apart from the origin.
+#[derive(Deserialize)]
+struct DestinationParams {
+    /// CRS code or TIPLOC of destination station
+    destination: Option<String>,
+    /// Latitude to arrive at instead of a station
+    #[serde(alias = "destination_lat")]
+    lat: Option<f64>,
+    /// Longitude to arrive at instead of a station
+    #[serde(alias = "destination_lon")]
+    lon: Option<f64>,
+}
+
+impl DestinationParams {
+    fn place(&self, walk: &WalkParams) -> actix_web::Result<Place> {
+        walk.place(&self.destination, self.lat, self.lon)
+    }
+}
+
+/// Minutes to draw isochrone polygons for unless the query gives some
+const DEFAULT_CUTOFFS: [u32; 3] = [60, 90, 120];
+/// Width of grid cells in metres
+const DEFAULT_CELL_SIZE: f64 = 1000.0;
+
+/// How to give the result of an isochrone
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Format {
+    /// The arrival time at each station
+    #[default]
+    Points,
+    /// The areas reachable within each cutoff
+    Polygons,
+    /// Minutes to each cell of a grid, up to the largest cutoff, as an ESRI
+    /// ASCII grid on the British National Grid
+    Grid,
+}
+
 #[derive(Deserialize)]
 struct Params {
-    /// TIPLOC of origin station
-    origin: String,
     /// Departure date
     date: NaiveDate,
     /// Departure time
     time: NaiveTime,
+    #[serde(default)]
+    format: Format,
+    /// Comma separated minutes to draw polygons for
+    cutoffs: Option<String>,
+    /// Width of grid cells in metres
+    cell_size: Option<f64>,
+}
+
+fn parse_cutoffs(cutoffs: &Option<String>) -> actix_web::Result<Vec<u32>> {
+    let Some(cutoffs) = cutoffs else {
+        return Ok(DEFAULT_CUTOFFS.to_vec());
+    };
+
+    cutoffs
+        .split(',')
+        .map(|c| {
+            c.trim()
+                .parse()
+                .map_err(|_| error::ErrorBadRequest(format!("Invalid cutoff '{c}'")))
+        })
+        .collect()
 }
 
 #[get("/isochrone")]
 async fn isochrone(
     params: web::Query<Params>,
-    csa: web::Data<Arc<ConnectionScan>>,
+    origin: web::Query<OriginParams>,
+    filters: web::Query<FilterParams>,
+    walk: web::Query<WalkParams>,
+    live: web::Data<Live>,
+) -> actix_web::Result<String> {
+    let csa = live.csa();
+    let origin = origin.place(&walk)?;
+    let date = params.date;
+    let start_time = params.time;
+
+    let start_time = NaiveDateTime::new(date, start_time);
+
+    let options = filters.options()?;
+
+    match params.format {
+        Format::Points => csa.departure_isochrone(&origin, start_time, &options),
+        Format::Polygons => csa.isochrone_polygons(
+            &origin,
+            start_time,
+            &options,
+            &parse_cutoffs(&params.cutoffs)?,
+            walk.walking_speed,
+        ),
+        Format::Grid => csa.isochrone_grid(
+            &origin,
+            start_time,
+            &options,
+            parse_cutoffs(&params.cutoffs)?
+                .into_iter()
+                .max()
+                .ok_or_else(|| error::ErrorBadRequest("No cutoffs given"))?,
+            params.cell_size.unwrap_or(DEFAULT_CELL_SIZE),
+            walk.walking_speed,
+        ),
+    }
+    .map_err(error::ErrorBadRequest)
+}
+
+#[derive(Deserialize)]
+struct ArriveByParams {
+    /// Arrival date
+    date: NaiveDate,
+    /// Latest arrival time
+    time: NaiveTime,
+}
+
+#[get("/arrive_by")]
+async fn arrive_by(
+    params: web::Query<ArriveByParams>,
+    destination: web::Query<DestinationParams>,
+    walk: web::Query<WalkParams>,
+    live: web::Data<Live>,
+) -> actix_web::Result<String> {
+    let csa = live.csa();
+    let destination = destination.place(&walk)?;
+    let date = params.date;
+    let deadline = params.time;
+
+    csa.arrival_isochrone(&destination, NaiveDateTime::new(date, deadline))
+        .map_err(error::ErrorBadRequest)
+}
+
+#[derive(Deserialize)]
+struct JourneyParams {
+    /// Departure date
+    date: NaiveDate,
+    /// Departure time
+    time: NaiveTime,
+}
+
+#[get("/journey")]
+async fn journey(
+    params: web::Query<JourneyParams>,
+    origin: web::Query<OriginParams>,
+    destination: web::Query<DestinationParams>,
+    filters: web::Query<FilterParams>,
+    walk: web::Query<WalkParams>,
+    live: web::Data<Live>,
 ) -> actix_web::Result<String> {
-    let origin = StopId::new(&params.origin);
+    let csa = live.csa();
+    let origin = origin.place(&walk)?;
+    let destination = destination.place(&walk)?;
     let date = params.date;
     let start_time = params.time;
 
-    csa.departure_isochrone(origin, NaiveDateTime::new(date, start_time))
-        .map_err(|err| error::ErrorBadRequest(err))
+    let options = filters.options()?;
+
+    csa.journey(
+        &origin,
+        &destination,
+        NaiveDateTime::new(date, start_time),
+        &options,
+    )
+    .map_err(error::ErrorBadRequest)
+}
+
+#[derive(Deserialize)]
+struct ProfileParams {
+    /// Departure date
+    date: NaiveDate,
+    /// Start of the departure window
+    start: NaiveTime,
+    /// End of the departure window
+    end: NaiveTime,
+}
+
+#[get("/profile")]
+async fn profile(
+    params: web::Query<ProfileParams>,
+    origin: web::Query<OriginParams>,
+    walk: web::Query<WalkParams>,
+    live: web::Data<Live>,
+) -> actix_web::Result<String> {
+    let csa = live.csa();
+    let origin = origin.place(&walk)?;
+
+    csa.departure_profile(&origin, params.date, params.start, params.end)
+        .map_err(error::ErrorBadRequest)
+}
+
+#[derive(Deserialize)]
+struct MatrixParams {
+    /// Departure date
+    date: NaiveDate,
+    /// Departure time
+    time: NaiveTime,
+    #[serde(default)]
+    format: MatrixFormat,
+}
+
+#[get("/od_matrix")]
+async fn od_matrix(
+    params: web::Query<MatrixParams>,
+    filters: web::Query<FilterParams>,
+    live: web::Data<Live>,
+) -> actix_web::Result<HttpResponse> {
+    let csa = live.csa();
+    let start_time = NaiveDateTime::new(params.date, params.time);
+    let options = filters.options()?;
+    let format = params.format;
+
+    // Scanning from every station takes a while, so keep it off the workers
+    let bytes = web::block(move || {
+        csa.od_matrix(start_time, &options)
+            .and_then(|matrix| format.write(&matrix))
+    })
+    .await?
+    .map_err(error::ErrorBadRequest)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .body(bytes))
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    /// Station name, alias, CRS code or TIPLOC
+    q: String,
+}
+
+#[get("/stops")]
+async fn stops(
+    params: web::Query<SearchParams>,
+    live: web::Data<Live>,
+) -> actix_web::Result<String> {
+    let csa = live.csa();
+    csa.search_stops(&params.q).map_err(error::ErrorBadRequest)
+}
+
+#[derive(Deserialize)]
+struct UpdateParams {
+    /// CIF update file in the updates directory, or a directory or zip
+    /// archive in it containing one
+    path: PathBuf,
+}
+
+#[post("/update")]
+async fn apply_update(
+    params: web::Query<UpdateParams>,
+    live: web::Data<Live>,
+) -> actix_web::Result<String> {
+    if live.updates.is_none() {
+        return Err(error::ErrorForbidden(
+            "Updates are disabled, start the server with --updates-dir to enable them",
+        ));
+    }
+
+    let path = params.into_inner().path;
+    let live = live.into_inner();
+
+    // Reading the update and rebuilding the scan takes a while, so keep it
+    // off the workers
+    let metadata = web::block(move || {
+        live.apply_update(&path)?;
+        anyhow::Ok(live.csa().metadata().clone())
+    })
+    .await?
+    .map_err(error::ErrorBadRequest)?;
+
+    serde_json::to_string(&metadata).map_err(error::ErrorInternalServerError)
+}
+
+#[get("/meta")]
+async fn meta(live: web::Data<Live>) -> actix_web::Result<String> {
+    let csa = live.csa();
+    serde_json::to_string(csa.metadata()).map_err(error::ErrorInternalServerError)
+}
+
+fn print_warnings(warnings: &[ParseError]) {
+    if !warnings.is_empty() {
+        eprintln!("Skipped {} malformed records:", warnings.len());
+        for warning in warnings.iter() {
+            eprintln!("  {warning}");
+        }
+    }
+}
+
+fn connection_scan(timetable: Timetable) -> ConnectionScan {
+    ConnectionScan::new(
+        timetable.metadata,
+        timetable.trips,
+        timetable.stops,
+        timetable.footpaths,
+        timetable.associations,
+        timetable.tiplocs,
+    )
+}
+
+/// Builds a connection scan from a copy of `timetable`, so that updates can
+/// still be applied to it
+fn copied_connection_scan(timetable: &Timetable) -> ConnectionScan {
+    ConnectionScan::new(
+        timetable.metadata.clone(),
+        timetable.trips.clone(),
+        timetable.stops.clone(),
+        timetable.footpaths.clone(),
+        timetable.associations.clone(),
+        timetable.tiplocs.clone(),
+    )
+}
+
+/// Where updates are applied from while the server runs
+struct Updates {
+    /// Canonical path of the only directory updates may be read from, so that
+    /// a request can't make the server open any other file
+    dir: PathBuf,
+    /// The timetable updates are applied to. The scan holds its own copy of
+    /// it, so keeping this roughly doubles the memory the server uses.
+    timetable: Mutex<Timetable>,
+}
+
+impl Updates {
+    /// `path` within the updates directory
+    fn resolve(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let resolved = self
+            .dir
+            .join(path)
+            .canonicalize()
+            .map_err(|_| anyhow!("{} not found in the updates directory", path.display()))?;
+
+        if !resolved.starts_with(&self.dir) {
+            return Err(anyhow!(
+                "{} is outside the updates directory",
+                path.display()
+            ));
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// The timetable being served
+struct Live {
+    /// Missing unless the server was started with an updates directory
+    updates: Option<Updates>,
+    csa: RwLock<Arc<ConnectionScan>>,
+    mode: ParseMode,
+}
+
+impl Live {
+    /// The scan of the latest timetable. Queries carry on with the one they
+    /// started with if an update is swapped in meanwhile.
+    fn csa(&self) -> Arc<ConnectionScan> {
+        self.csa
+            .read()
+            .expect("Lock should not be poisoned")
+            .clone()
+    }
+
+    /// Applies the update at `path` in the updates directory and swaps in a
+    /// scan of the result. Updates are applied one at a time.
+    fn apply_update(&self, path: &Path) -> anyhow::Result<()> {
+        let updates = self.updates.as_ref().context("Updates are disabled")?;
+        let resolved = updates.resolve(path)?;
+        let mut timetable = updates
+            .timetable
+            .lock()
+            .expect("Lock should not be poisoned");
+
+        let now = Instant::now();
+        let skipped = timetable.warnings.len();
+        timetable.apply_update(&resolved, self.mode)?;
+        print_warnings(&timetable.warnings[skipped..]);
+
+        let csa = Arc::new(copied_connection_scan(&timetable));
+        *self.csa.write().expect("Lock should not be poisoned") = csa;
+        println!("Applied {} in {:?}", path.display(), now.elapsed());
+
+        Ok(())
+    }
+}
+
+/// Reads the full timetable and applies the updates given on top of it
+fn read_timetable(args: &Args, mode: ParseMode) -> anyhow::Result<Timetable> {
+    let mut timetable = Timetable::read(&args.timetable, mode)?;
+    for update in args.updates.iter() {
+        timetable.apply_update(update, mode)?;
+    }
+
+    Ok(timetable)
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let timetable_path = std::env::args().nth(1).unwrap();
+    let args = Args::parse();
 
     let now = Instant::now();
-    let timetable = Timetable::read(&timetable_path).unwrap();
+    let mode = if args.lenient {
+        ParseMode::Lenient
+    } else {
+        ParseMode::Strict
+    };
+
+    let updates_dir = match args
+        .updates_dir
+        .as_deref()
+        .map(Path::canonicalize)
+        .transpose()
+    {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Failed to open updates directory: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let timetable = match read_timetable(&args, mode) {
+        Ok(timetable) => timetable,
+        Err(e) => {
+            eprintln!("Failed to read timetable: {e:#}");
+            std::process::exit(1);
+        }
+    };
     println!("Read timetable in {:?}", now.elapsed());
 
-    let connection_scanner = Arc::new(ConnectionScan::new(
-        timetable.trips,
-        timetable.stops,
-        timetable.footpaths,
-    ));
+    print_warnings(&timetable.warnings);
+
+    // Only keep the timetable alongside the scan if updates can be applied
+    // to it
+    let (connection_scanner, updates) = match updates_dir {
+        Some(dir) if args.command.is_none() => {
+            let csa = copied_connection_scan(&timetable);
+            let updates = Updates {
+                dir,
+                timetable: Mutex::new(timetable),
+            };
+            (csa, Some(updates))
+        }
+        _ => (connection_scan(timetable), None),
+    };
+    let connection_scanner = Arc::new(connection_scanner);
+
+    match args.command {
+        Some(Command::Grid(grid)) => return grid.write(&connection_scanner),
+        Some(Command::Matrix(matrix)) => return matrix.write(&connection_scanner),
+        None => {}
+    }
+
+    let live = web::Data::new(Live {
+        updates,
+        csa: RwLock::new(connection_scanner),
+        mode,
+    });
 
     HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(connection_scanner.clone()))
+            .app_data(live.clone())
             .service(isochrone)
+            .service(arrive_by)
+            .service(journey)
+            .service(profile)
+            .service(od_matrix)
+            .service(stops)
+            .service(meta)
+            .service(apply_update)
     })
     .bind(("127.0.0.1", 8080))?
     .run()
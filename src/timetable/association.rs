@@ -0,0 +1,62 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::timetable::{
+    stop::StopId,
+    trip::{TripId, TripType},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssociationCategory {
+    /// The associated train joins the base train (JJ)
+    Join,
+    /// The associated train divides from the base train (VV)
+    Divide,
+    /// The associated train is the next working of the base train (NP)
+    Next,
+}
+
+/// When the associated train runs relative to the base train
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateIndicator {
+    Same,
+    Next,
+    Previous,
+}
+
+impl DateIndicator {
+    /// Days from the base train's service day to the associated train's
+    pub fn offset(&self) -> i32 {
+        match self {
+            DateIndicator::Same => 0,
+            DateIndicator::Next => 1,
+            DateIndicator::Previous => -1,
+        }
+    }
+}
+
+/// A link between two trains from an AA record
+#[derive(Debug, Clone)]
+pub struct Association {
+    pub base_uid: TripId,
+    pub assoc_uid: TripId,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub days_run: [bool; 7],
+    /// Missing for cancellations
+    pub category: Option<AssociationCategory>,
+    pub date_indicator: DateIndicator,
+    pub location: StopId,
+    /// Whether the association is relevant to passengers, as opposed to only
+    /// for operating purposes
+    pub passenger: bool,
+    pub stp: TripType,
+}
+
+impl Association {
+    /// Whether the association applies on `date`, the base train's service day
+    pub fn runs_on(&self, date: NaiveDate) -> bool {
+        self.start_date <= date
+            && self.end_date >= date
+            && self.days_run[date.weekday().num_days_from_monday() as usize]
+    }
+}
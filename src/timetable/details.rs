@@ -0,0 +1,212 @@
+use serde::Serialize;
+
+use crate::timetable::parse::{field, FieldError, Named};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrainStatus {
+    Bus,
+    Freight,
+    PassengerAndParcels,
+    Ship,
+    Trip,
+    Unknown,
+}
+
+impl TrainStatus {
+    /// Parses a CIF train status, where digits are the STP equivalents of the
+    /// letter codes
+    pub fn from_code(code: char) -> Self {
+        match code {
+            'B' | '5' => TrainStatus::Bus,
+            'F' | '2' => TrainStatus::Freight,
+            'P' | '1' => TrainStatus::PassengerAndParcels,
+            'S' | '4' => TrainStatus::Ship,
+            'T' | '3' => TrainStatus::Trip,
+            _ => TrainStatus::Unknown,
+        }
+    }
+}
+
+/// Statuses are given by their letter codes
+impl Named for TrainStatus {
+    const ALL: &'static [Self] = &[
+        TrainStatus::Bus,
+        TrainStatus::Freight,
+        TrainStatus::PassengerAndParcels,
+        TrainStatus::Ship,
+        TrainStatus::Trip,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            TrainStatus::Bus => "B",
+            TrainStatus::Freight => "F",
+            TrainStatus::PassengerAndParcels => "P",
+            TrainStatus::Ship => "S",
+            TrainStatus::Trip => "T",
+            TrainStatus::Unknown => " ",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatingClass {
+    FirstAndStandard,
+    StandardOnly,
+}
+
+impl SeatingClass {
+    fn from_code(code: char) -> Self {
+        match code {
+            'S' => SeatingClass::StandardOnly,
+            _ => SeatingClass::FirstAndStandard,
+        }
+    }
+}
+
+impl Named for SeatingClass {
+    const ALL: &'static [Self] = &[SeatingClass::FirstAndStandard, SeatingClass::StandardOnly];
+
+    fn name(self) -> &'static str {
+        match self {
+            SeatingClass::FirstAndStandard => "first_and_standard",
+            SeatingClass::StandardOnly => "standard_only",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sleepers {
+    FirstAndStandard,
+    FirstOnly,
+    StandardOnly,
+}
+
+impl Sleepers {
+    fn from_code(code: char) -> Option<Self> {
+        match code {
+            'B' => Some(Sleepers::FirstAndStandard),
+            'F' => Some(Sleepers::FirstOnly),
+            'S' => Some(Sleepers::StandardOnly),
+            _ => None,
+        }
+    }
+}
+
+impl Named for Sleepers {
+    const ALL: &'static [Self] = &[
+        Sleepers::FirstAndStandard,
+        Sleepers::FirstOnly,
+        Sleepers::StandardOnly,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Sleepers::FirstAndStandard => "first_and_standard",
+            Sleepers::FirstOnly => "first_only",
+            Sleepers::StandardOnly => "standard_only",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reservations {
+    Compulsory,
+    BicyclesEssential,
+    Recommended,
+    Possible,
+    None,
+}
+
+impl Reservations {
+    fn from_code(code: char) -> Self {
+        match code {
+            'A' => Reservations::Compulsory,
+            'E' => Reservations::BicyclesEssential,
+            'R' => Reservations::Recommended,
+            'S' => Reservations::Possible,
+            _ => Reservations::None,
+        }
+    }
+}
+
+impl Named for Reservations {
+    const ALL: &'static [Self] = &[
+        Reservations::Compulsory,
+        Reservations::BicyclesEssential,
+        Reservations::Recommended,
+        Reservations::Possible,
+        Reservations::None,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Reservations::Compulsory => "compulsory",
+            Reservations::BicyclesEssential => "bicycles_essential",
+            Reservations::Recommended => "recommended",
+            Reservations::Possible => "possible",
+            Reservations::None => "none",
+        }
+    }
+}
+
+/// Service attributes from a BS record
+#[derive(Debug, Clone, Serialize)]
+pub struct TripDetails {
+    pub status: TrainStatus,
+    /// Two character train category, e.g. `XX` for express passenger
+    pub category: String,
+    /// Signalling ID, the headcode shown in train describers
+    pub signalling_id: String,
+    /// Headcode used by the National Reservation System
+    pub headcode: String,
+    pub service_code: String,
+    /// e.g. `EMU`, `DMU`, `HST` or `E` for electric locomotive
+    pub power_type: String,
+    pub timing_load: String,
+    /// Maximum speed in miles per hour
+    pub speed: Option<u32>,
+    /// Up to six single character operating characteristics codes
+    pub operating_characteristics: String,
+    pub seating_class: SeatingClass,
+    pub sleepers: Option<Sleepers>,
+    pub reservations: Reservations,
+    /// Up to four single character catering codes
+    pub catering: String,
+    pub service_branding: String,
+}
+
+impl TripDetails {
+    /// Parses the service attributes, which start at the train category at
+    /// the 0-based index `start`. A BS record holds them from column 31, and
+    /// a CR record from column 11.
+    pub fn parse(status: TrainStatus, line: &str, start: usize) -> Result<Self, FieldError> {
+        let text = |from: usize, to: usize| {
+            field(line, start + from, start + to).map(|f| f.trim().to_owned())
+        };
+        let code = |idx: usize| {
+            field(line, start + idx, start + idx + 1).map(|f| f.chars().next().unwrap_or(' '))
+        };
+
+        Ok(Self {
+            status,
+            category: text(0, 2)?,
+            signalling_id: text(2, 6)?,
+            headcode: text(6, 10)?,
+            service_code: text(11, 19)?,
+            power_type: text(20, 23)?,
+            timing_load: text(23, 27)?,
+            speed: text(27, 30)?.parse().ok(),
+            operating_characteristics: text(30, 36)?,
+            seating_class: SeatingClass::from_code(code(36)?),
+            sleepers: Sleepers::from_code(code(37)?),
+            reservations: Reservations::from_code(code(38)?),
+            catering: text(40, 44)?,
+            service_branding: text(44, 48)?,
+        })
+    }
+}
@@ -1,6 +1,17 @@
-use anyhow::{anyhow, Context};
+use std::collections::HashMap;
 
-enum Mode {
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+
+use crate::timetable::{
+    parse::{FieldError, Named},
+    parse_days_run,
+};
+
+/// How a link between stations is travelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
     Bus,
     Tube,
     Walk,
@@ -10,51 +21,264 @@ enum Mode {
     Transfer,
 }
 
-#[allow(unused)]
+/// Files give modes in upper case, and queries in lower case
+impl Named for Mode {
+    const ALL: &'static [Self] = &[
+        Mode::Bus,
+        Mode::Tube,
+        Mode::Walk,
+        Mode::Ferry,
+        Mode::Metro,
+        Mode::Tram,
+        Mode::Transfer,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Bus => "bus",
+            Mode::Tube => "tube",
+            Mode::Walk => "walk",
+            Mode::Ferry => "ferry",
+            Mode::Metro => "metro",
+            Mode::Tram => "tram",
+            Mode::Transfer => "transfer",
+        }
+    }
+}
+
+/// When a link can be used. Times of day run past midnight if `end_time` is
+/// before `start_time`.
+#[derive(Debug, Clone, Copy)]
+pub struct Availability {
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub days_run: [bool; 7],
+}
+
+impl Availability {
+    pub fn always() -> Self {
+        Self {
+            start_date: None,
+            end_date: None,
+            start_time: NaiveTime::MIN,
+            end_time: NaiveTime::from_hms_opt(23, 59, 59).expect("Should be a valid time"),
+            days_run: [true; 7],
+        }
+    }
+
+    /// Whether the link can be set off along at `at`
+    pub fn covers(&self, at: NaiveDateTime) -> bool {
+        let date = at.date();
+        let time = at.time();
+
+        let in_dates =
+            self.start_date.is_none_or(|d| d <= date) && self.end_date.is_none_or(|d| date <= d);
+
+        let in_hours = if self.start_time <= self.end_time {
+            self.start_time <= time && time <= self.end_time
+        } else {
+            self.start_time <= time || time <= self.end_time
+        };
+
+        in_dates && in_hours && self.days_run[date.weekday().num_days_from_monday() as usize]
+    }
+}
+
+#[derive(Clone)]
 pub struct Footpath {
     pub from_crs: String,
     pub to_crs: String,
-    mode: Mode,
+    pub mode: Mode,
     pub time: u32,
+    pub availability: Availability,
+}
+
+/// Parses a field given as `HHMM`, where `2400` is the end of the day
+fn parse_time(s: &str) -> Result<NaiveTime, FieldError> {
+    if s == "2400" {
+        return Ok(NaiveTime::from_hms_opt(23, 59, 59).expect("Should be a valid time"));
+    }
+
+    NaiveTime::parse_from_str(s, "%H%M").map_err(|_| FieldError::new(format!("invalid time '{s}'")))
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, FieldError> {
+    NaiveDate::parse_from_str(s, "%d/%m/%Y")
+        .map_err(|_| FieldError::new(format!("invalid date '{s}'")))
+}
+
+/// Parses the days a link runs, as seven `0`s or `1`s from Monday
+fn parse_days(s: &str) -> Result<[bool; 7], FieldError> {
+    if s.len() != 7 || !s.bytes().all(|d| d == b'0' || d == b'1') {
+        return Err(FieldError::new(format!("invalid days '{s}'")));
+    }
+
+    Ok(parse_days_run(s))
+}
+
+fn parse_mode(s: &str) -> Result<Mode, FieldError> {
+    Mode::parse(s).ok_or_else(|| FieldError::new(format!("invalid mode '{s}'")))
+}
+
+fn parse_minutes(s: &str) -> Result<u32, FieldError> {
+    s.parse::<u32>()
+        .map(|m| m * 60)
+        .map_err(|_| FieldError::new(format!("invalid time '{s}'")))
 }
 
 impl Footpath {
-    pub fn parse(s: &str) -> anyhow::Result<Self> {
-        let entries = s.split(',').collect::<Vec<_>>();
-
-        let mode = match entries[0].split_once('=').expect("Should be a K=V pair").1 {
-            "BUS" => Ok(Mode::Bus),
-            "TUBE" => Ok(Mode::Tube),
-            "WALK" => Ok(Mode::Walk),
-            "FERRY" => Ok(Mode::Ferry),
-            "METRO" => Ok(Mode::Metro),
-            "TRAM" => Ok(Mode::Tram),
-            "TRANSFER" => Ok(Mode::Transfer),
-            _ => Err(anyhow!("Invalid mode")),
-        }?;
-
-        let from_crs = entries[1]
-            .split_once('=')
-            .context("Should be a K=V pair")?
-            .1
-            .to_owned();
-        let to_crs = entries[2]
-            .split_once('=')
-            .context("Should be a K=V pair")?
-            .1
-            .to_owned();
-        let time = entries[3]
-            .split_once('=')
-            .context("Should be a K=V pair")?
-            .1
-            .parse::<u32>();
-
-        time.map(|t| Footpath {
-            from_crs,
-            to_crs,
-            mode,
-            time: t * 60,
+    /// Parses a line of the `.ALF` file: comma separated `K=V` pairs giving
+    /// the mode, origin, destination and time in minutes, and optionally the
+    /// hours (`S`, `E`), dates (`F`, `U`) and days (`R`) it runs
+    pub fn parse(s: &str) -> Result<Self, FieldError> {
+        let entries: HashMap<&str, &str> = s
+            .split(',')
+            .filter_map(|e| e.trim().split_once('='))
+            .collect();
+
+        let value = |key: &str| {
+            entries
+                .get(key)
+                .copied()
+                .ok_or_else(|| FieldError::new(format!("missing {key}= entry")))
+        };
+
+        let mut availability = Availability::always();
+        if let Some(start) = entries.get("S") {
+            availability.start_time = parse_time(start)?;
+        }
+        if let Some(end) = entries.get("E") {
+            availability.end_time = parse_time(end)?;
+        }
+        if let Some(start) = entries.get("F") {
+            availability.start_date = Some(parse_date(start)?);
+        }
+        if let Some(end) = entries.get("U") {
+            availability.end_date = Some(parse_date(end)?);
+        }
+        if let Some(days) = entries.get("R") {
+            availability.days_run = parse_days(days)?;
+        }
+
+        Ok(Footpath {
+            from_crs: value("O")?.to_owned(),
+            to_crs: value("D")?.to_owned(),
+            mode: parse_mode(value("M")?)?,
+            time: parse_minutes(value("T")?)?,
+            availability,
         })
-        .context("Failed to parse time")
+    }
+
+    /// Parses a line of the `.FLF` file, e.g. `ADDITIONAL LINK: WALK BETWEEN
+    /// EUS AND KGX IN 10 MINUTES`. Fixed links can be used either way at any
+    /// time, so this gives one footpath in each direction. Other lines are
+    /// skipped.
+    pub fn parse_fixed_link(s: &str) -> Result<Vec<Self>, FieldError> {
+        let Some(link) = s.trim().strip_prefix("ADDITIONAL LINK:") else {
+            return Ok(vec![]);
+        };
+
+        let words = link.split_whitespace().collect::<Vec<_>>();
+        let [mode, "BETWEEN", from_crs, "AND", to_crs, "IN", time, "MINUTES"] = words[..] else {
+            return Err(FieldError::new(format!(
+                "invalid fixed link '{}'",
+                s.trim()
+            )));
+        };
+
+        let mode = parse_mode(mode)?;
+        let time = parse_minutes(time)?;
+
+        Ok(vec![
+            Footpath {
+                from_crs: from_crs.to_owned(),
+                to_crs: to_crs.to_owned(),
+                mode,
+                time,
+                availability: Availability::always(),
+            },
+            Footpath {
+                from_crs: to_crs.to_owned(),
+                to_crs: from_crs.to_owned(),
+                mode,
+                time,
+                availability: Availability::always(),
+            },
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn alf_line_gives_mode_time_and_availability() {
+        let footpath = Footpath::parse(
+            "M=TUBE,O=EUS,D=KGX,T=10,S=0530,E=0030,F=01/03/2024,U=31/03/2024,P=4,R=1111100",
+        )
+        .unwrap();
+
+        assert_eq!(footpath.from_crs, "EUS");
+        assert_eq!(footpath.to_crs, "KGX");
+        assert_eq!(footpath.mode, Mode::Tube);
+        assert_eq!(footpath.time, 600);
+
+        let availability = footpath.availability;
+        assert!(availability.covers(at("2024-03-04 08:00")));
+        // The hours run past midnight
+        assert!(availability.covers(at("2024-03-05 00:15")));
+        assert!(!availability.covers(at("2024-03-05 03:00")));
+        // Weekdays only
+        assert!(!availability.covers(at("2024-03-09 08:00")));
+        // Outside the dates
+        assert!(!availability.covers(at("2024-04-01 08:00")));
+    }
+
+    #[test]
+    fn fixed_link_runs_both_ways_at_any_time() {
+        let links =
+            Footpath::parse_fixed_link("ADDITIONAL LINK: WALK BETWEEN EUS AND KGX IN 10 MINUTES")
+                .unwrap();
+
+        let ends = links
+            .iter()
+            .map(|l| (l.from_crs.as_str(), l.to_crs.as_str(), l.mode, l.time))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ends,
+            vec![
+                ("EUS", "KGX", Mode::Walk, 600),
+                ("KGX", "EUS", Mode::Walk, 600)
+            ]
+        );
+        assert!(links[0].availability.covers(at("2024-03-09 03:00")));
+
+        assert!(Footpath::parse_fixed_link("END").unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        let error = |line: &str| Footpath::parse(line).err().unwrap().message;
+
+        assert_eq!(error("M=WALK,O=EUS,T=10"), "missing D= entry");
+        assert_eq!(error("M=SKIP,O=EUS,D=KGX,T=10"), "invalid mode 'SKIP'");
+        assert_eq!(
+            error("M=WALK,O=EUS,D=KGX,T=10,S=2500"),
+            "invalid time '2500'"
+        );
+        assert_eq!(
+            error("M=WALK,O=EUS,D=KGX,T=10,R=11111111"),
+            "invalid days '11111111'"
+        );
+        assert_eq!(error("M=WALK,O=EUS,D=KGX,T=10,R=abc"), "invalid days 'abc'");
+
+        assert!(Footpath::parse_fixed_link("ADDITIONAL LINK: WALK BETWEEN EUS AND KGX").is_err());
     }
 }
@@ -0,0 +1,235 @@
+use geo_types::Point;
+
+/// An Ordnance Survey National Grid reference, in metres on the OSGB36 datum
+#[derive(Debug, Clone, Copy)]
+pub struct GridReference {
+    pub easting: f64,
+    pub northing: f64,
+}
+
+/// Airy 1830 ellipsoid, used by OSGB36
+const AIRY_A: f64 = 6_377_563.396;
+const AIRY_B: f64 = 6_356_256.909;
+
+/// GRS80 ellipsoid, which WGS84 matches to well within a millimetre
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_B: f64 = 6_356_752.314_140;
+
+/// National Grid transverse Mercator projection
+const SCALE_FACTOR: f64 = 0.999_601_271_7;
+const TRUE_ORIGIN_LAT: f64 = 49.0;
+const TRUE_ORIGIN_LON: f64 = -2.0;
+const FALSE_EASTING: f64 = 400_000.0;
+const FALSE_NORTHING: f64 = -100_000.0;
+
+/// Helmert transformation from OSGB36 to WGS84: translation in metres, scale
+/// in parts per million and rotation in arc seconds. Accurate to a few metres.
+const TX: f64 = 446.448;
+const TY: f64 = -125.157;
+const TZ: f64 = 542.060;
+const SCALE_PPM: f64 = -20.4894;
+const RX: f64 = 0.1502;
+const RY: f64 = 0.2470;
+const RZ: f64 = 0.8421;
+
+/// Meridional arc from the true origin to `lat`, on the Airy ellipsoid
+fn meridional_arc(lat: f64) -> f64 {
+    let (b, f0) = (AIRY_B, SCALE_FACTOR);
+    let n = (AIRY_A - AIRY_B) / (AIRY_A + AIRY_B);
+    let (n2, n3) = (n * n, n * n * n);
+    let lat0 = TRUE_ORIGIN_LAT.to_radians();
+
+    let (d, s) = (lat - lat0, lat + lat0);
+    b * f0
+        * ((1.0 + n + 1.25 * n2 + 1.25 * n3) * d
+            - (3.0 * n + 3.0 * n2 + 2.625 * n3) * d.sin() * s.cos()
+            + (1.875 * n2 + 1.875 * n3) * (2.0 * d).sin() * (2.0 * s).cos()
+            - (35.0 / 24.0) * n3 * (3.0 * d).sin() * (3.0 * s).cos())
+}
+
+impl GridReference {
+    /// Converts to WGS84 longitude and latitude
+    pub fn to_wgs84(self) -> Point {
+        let (lat, lon) = self.to_osgb36_lat_lon();
+        let (x, y, z) = to_cartesian(lat, lon, AIRY_A, AIRY_B);
+        let (x, y, z) = helmert(x, y, z, 1.0);
+        let (lat, lon) = from_cartesian(x, y, z, WGS84_A, WGS84_B);
+
+        Point::new(lon.to_degrees(), lat.to_degrees())
+    }
+
+    /// Converts from WGS84 longitude and latitude, reversing `to_wgs84` to
+    /// within a few metres
+    pub fn from_wgs84(point: Point) -> Self {
+        let (lat, lon) = (point.y().to_radians(), point.x().to_radians());
+        let (x, y, z) = to_cartesian(lat, lon, WGS84_A, WGS84_B);
+        let (x, y, z) = helmert(x, y, z, -1.0);
+        let (lat, lon) = from_cartesian(x, y, z, AIRY_A, AIRY_B);
+
+        Self::from_osgb36_lat_lon(lat, lon)
+    }
+
+    /// Applies the National Grid projection to latitude and longitude in
+    /// radians on the Airy ellipsoid
+    fn from_osgb36_lat_lon(lat: f64, lon: f64) -> Self {
+        let (a, b, f0) = (AIRY_A, AIRY_B, SCALE_FACTOR);
+        let e2 = 1.0 - (b * b) / (a * a);
+
+        let (sin, cos, tan) = (lat.sin(), lat.cos(), lat.tan());
+        let nu = a * f0 / (1.0 - e2 * sin * sin).sqrt();
+        let rho = a * f0 * (1.0 - e2) / (1.0 - e2 * sin * sin).powf(1.5);
+        let eta2 = nu / rho - 1.0;
+        let (tan2, tan4) = (tan * tan, tan.powi(4));
+
+        let i = meridional_arc(lat) + FALSE_NORTHING;
+        let ii = nu / 2.0 * sin * cos;
+        let iii = nu / 24.0 * sin * cos.powi(3) * (5.0 - tan2 + 9.0 * eta2);
+        let iiia = nu / 720.0 * sin * cos.powi(5) * (61.0 - 58.0 * tan2 + tan4);
+        let iv = nu * cos;
+        let v = nu / 6.0 * cos.powi(3) * (nu / rho - tan2);
+        let vi = nu / 120.0
+            * cos.powi(5)
+            * (5.0 - 18.0 * tan2 + tan4 + 14.0 * eta2 - 58.0 * tan2 * eta2);
+
+        let dl = lon - TRUE_ORIGIN_LON.to_radians();
+        Self {
+            easting: FALSE_EASTING + iv * dl + v * dl.powi(3) + vi * dl.powi(5),
+            northing: i + ii * dl.powi(2) + iii * dl.powi(4) + iiia * dl.powi(6),
+        }
+    }
+
+    /// Inverts the National Grid projection, giving latitude and longitude in
+    /// radians on the Airy ellipsoid
+    fn to_osgb36_lat_lon(self) -> (f64, f64) {
+        let (a, b, f0) = (AIRY_A, AIRY_B, SCALE_FACTOR);
+        let lon0 = TRUE_ORIGIN_LON.to_radians();
+        let e2 = 1.0 - (b * b) / (a * a);
+
+        let mut lat = TRUE_ORIGIN_LAT.to_radians();
+        let mut m = 0.0;
+        loop {
+            lat += (self.northing - FALSE_NORTHING - m) / (a * f0);
+            m = meridional_arc(lat);
+            if (self.northing - FALSE_NORTHING - m).abs() < 0.000_01 {
+                break;
+            }
+        }
+
+        let (sin, cos, tan) = (lat.sin(), lat.cos(), lat.tan());
+        let nu = a * f0 / (1.0 - e2 * sin * sin).sqrt();
+        let rho = a * f0 * (1.0 - e2) / (1.0 - e2 * sin * sin).powf(1.5);
+        let eta2 = nu / rho - 1.0;
+
+        let (tan2, tan4, tan6) = (tan * tan, tan.powi(4), tan.powi(6));
+        let sec = 1.0 / cos;
+
+        let vii = tan / (2.0 * rho * nu);
+        let viii = tan / (24.0 * rho * nu.powi(3)) * (5.0 + 3.0 * tan2 + eta2 - 9.0 * tan2 * eta2);
+        let ix = tan / (720.0 * rho * nu.powi(5)) * (61.0 + 90.0 * tan2 + 45.0 * tan4);
+        let x = sec / nu;
+        let xi = sec / (6.0 * nu.powi(3)) * (nu / rho + 2.0 * tan2);
+        let xii = sec / (120.0 * nu.powi(5)) * (5.0 + 28.0 * tan2 + 24.0 * tan4);
+        let xiia =
+            sec / (5040.0 * nu.powi(7)) * (61.0 + 662.0 * tan2 + 1320.0 * tan4 + 720.0 * tan6);
+
+        let de = self.easting - FALSE_EASTING;
+        let lat = lat - vii * de.powi(2) + viii * de.powi(4) - ix * de.powi(6);
+        let lon = lon0 + x * de - xi * de.powi(3) + xii * de.powi(5) - xiia * de.powi(7);
+
+        (lat, lon)
+    }
+}
+
+fn to_cartesian(lat: f64, lon: f64, a: f64, b: f64) -> (f64, f64, f64) {
+    let e2 = 1.0 - (b * b) / (a * a);
+    let nu = a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+
+    (
+        nu * lat.cos() * lon.cos(),
+        nu * lat.cos() * lon.sin(),
+        (1.0 - e2) * nu * lat.sin(),
+    )
+}
+
+/// Helmert transformation from OSGB36 to WGS84, or back again with a
+/// `direction` of -1
+fn helmert(x: f64, y: f64, z: f64, direction: f64) -> (f64, f64, f64) {
+    let s = 1.0 + direction * SCALE_PPM / 1e6;
+    let arc_second = direction * (1.0f64 / 3600.0).to_radians();
+    let (rx, ry, rz) = (RX * arc_second, RY * arc_second, RZ * arc_second);
+    let (tx, ty, tz) = (direction * TX, direction * TY, direction * TZ);
+
+    (
+        tx + s * x - rz * y + ry * z,
+        ty + rz * x + s * y - rx * z,
+        tz - ry * x + rx * y + s * z,
+    )
+}
+
+fn from_cartesian(x: f64, y: f64, z: f64, a: f64, b: f64) -> (f64, f64) {
+    let e2 = 1.0 - (b * b) / (a * a);
+    let p = (x * x + y * y).sqrt();
+
+    let mut lat = z.atan2(p * (1.0 - e2));
+    loop {
+        let nu = a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        let next = (z + e2 * nu * lat.sin()).atan2(p);
+        if (next - lat).abs() < 1e-12 {
+            break;
+        }
+        lat = next;
+    }
+
+    (lat, y.atan2(x))
+}
+
+/// Great circle distance between two WGS84 points in metres
+pub fn distance(a: Point, b: Point) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+
+    let (lat1, lat2) = (a.y().to_radians(), b.y().to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.x() - a.x()).to_radians();
+
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn projection_matches_ordnance_survey_worked_example() {
+        // From the OS guide to coordinate systems in Great Britain, Annex C
+        let grid = GridReference {
+            easting: 651_409.903,
+            northing: 313_177.270,
+        };
+
+        let (lat, lon) = grid.to_osgb36_lat_lon();
+        // 52° 39' 27.2531" N, 1° 43' 4.5177" E
+        assert!((lat.to_degrees() - 52.657_570_3).abs() < 1e-7);
+        assert!((lon.to_degrees() - 1.717_921_6).abs() < 1e-7);
+
+        let back = GridReference::from_osgb36_lat_lon(lat, lon);
+        assert!((back.easting - grid.easting).abs() < 0.001);
+        assert!((back.northing - grid.northing).abs() < 0.001);
+    }
+
+    #[test]
+    fn station_grid_reference_is_where_the_station_is() {
+        // London Kings Cross, as the MSN gives it to the nearest 100m
+        let grid = GridReference {
+            easting: 530_200.0,
+            northing: 183_000.0,
+        };
+
+        let kgx = Point::new(-0.1229, 51.5309);
+        assert!(distance(grid.to_wgs84(), kgx) < 150.0);
+
+        let back = GridReference::from_wgs84(grid.to_wgs84());
+        assert!((back.easting - grid.easting).abs() < 1.0);
+        assert!((back.northing - grid.northing).abs() < 1.0);
+    }
+}
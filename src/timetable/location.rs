@@ -1,21 +1,78 @@
 use chrono::NaiveTime;
+use serde::Serialize;
 
 use crate::timetable::stop::StopId;
 
-#[derive(Debug)]
+/// Passenger activities at a location, from the CIF activity field
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Activities {
+    /// Passengers may board here
+    pub pick_up: bool,
+    /// Passengers may alight here
+    pub set_down: bool,
+    /// Only stops if requested
+    pub request_stop: bool,
+    /// Train begins here
+    pub train_begins: bool,
+    /// Train finishes here
+    pub train_finishes: bool,
+}
+
+impl Activities {
+    /// Parses the activity field, which holds up to six two-character codes
+    pub fn parse(field: &str) -> Self {
+        let mut activities = Activities::default();
+
+        for code in field.as_bytes().chunks(2) {
+            match code {
+                b"T " => {
+                    activities.pick_up = true;
+                    activities.set_down = true;
+                }
+                b"U " => activities.pick_up = true,
+                b"D " => activities.set_down = true,
+                b"R " => {
+                    activities.request_stop = true;
+                    activities.pick_up = true;
+                    activities.set_down = true;
+                }
+                b"TB" => {
+                    activities.train_begins = true;
+                    activities.pick_up = true;
+                }
+                b"TF" => {
+                    activities.train_finishes = true;
+                    activities.set_down = true;
+                }
+                _ => {}
+            }
+        }
+
+        activities
+    }
+
+    pub fn is_passenger_stop(&self) -> bool {
+        self.pick_up || self.set_down
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Location {
     Origin {
         tiploc: StopId,
         departure_time: NaiveTime,
+        activities: Activities,
     },
     Intermediate {
         tiploc: StopId,
         arrival_time: NaiveTime,
         departure_time: NaiveTime,
+        activities: Activities,
     },
     Destination {
         tiploc: StopId,
         arrival_time: NaiveTime,
+        activities: Activities,
     },
 }
 
@@ -43,4 +100,12 @@ impl Location {
             Location::Destination { arrival_time, .. } => Some(*arrival_time),
         }
     }
+
+    pub fn activities(&self) -> Activities {
+        match self {
+            Location::Origin { activities, .. } => *activities,
+            Location::Intermediate { activities, .. } => *activities,
+            Location::Destination { activities, .. } => *activities,
+        }
+    }
 }
@@ -0,0 +1,62 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use crate::timetable::parse::{date, field, time, FieldError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateType {
+    /// A complete extract of the timetable
+    Full,
+    /// Changes to apply on top of a previous extract
+    Update,
+}
+
+/// Details of a CIF extract from its HD record
+#[derive(Debug, Clone, Serialize)]
+pub struct TimetableMetadata {
+    /// Identifies the user and extract, e.g. `TPS.UDFROC1.PD240301`
+    pub mainframe_identity: String,
+    pub extracted_at: NaiveDateTime,
+    /// Reference of this file, e.g. `DFROC1M`
+    pub file_reference: String,
+    /// Reference of the file before this one, if any
+    pub last_file_reference: Option<String>,
+    pub update_type: UpdateType,
+    /// First date the timetable covers
+    pub valid_from: NaiveDate,
+    /// Last date the timetable covers
+    pub valid_until: NaiveDate,
+}
+
+impl TimetableMetadata {
+    pub fn parse(line: &str) -> Result<Self, FieldError> {
+        let update_type = match field(line, 46, 47)? {
+            "F" => UpdateType::Full,
+            "U" => UpdateType::Update,
+            indicator => {
+                return Err(FieldError::at(
+                    46,
+                    format!("invalid update indicator '{indicator}'"),
+                ))
+            }
+        };
+
+        let last_file_reference = field(line, 39, 46)?.trim();
+
+        Ok(Self {
+            mainframe_identity: field(line, 2, 22)?.trim().to_owned(),
+            extracted_at: NaiveDateTime::new(date(line, 22, "%d%m%y")?, time(line, 28)?),
+            file_reference: field(line, 32, 39)?.trim().to_owned(),
+            last_file_reference: Some(last_file_reference.to_owned()).filter(|r| !r.is_empty()),
+            update_type,
+            valid_from: date(line, 48, "%d%m%y")?,
+            valid_until: date(line, 54, "%d%m%y")?,
+        })
+    }
+
+    /// Whether the timetable covers `date`
+    pub fn covers(&self, date: NaiveDate) -> bool {
+        self.valid_from <= date && date <= self.valid_until
+    }
+}
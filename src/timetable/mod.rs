@@ -1,39 +1,37 @@
+pub mod association;
+pub mod details;
 pub mod footpath;
+pub mod grid;
 pub mod location;
+pub mod metadata;
+pub mod parse;
+pub mod source;
 pub mod stop;
+pub mod tiploc;
 pub mod trip;
+pub mod update;
 
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufRead, BufReader},
-    path::{Path, PathBuf},
-};
+use std::{collections::HashMap, path::Path};
 
-use anyhow::Context;
-use chrono::{NaiveDate, NaiveTime};
+use anyhow::{anyhow, Context};
+use geo_types::Point;
 use serde::Deserialize;
 
 use crate::timetable::{
+    association::{Association, AssociationCategory, DateIndicator},
+    details::{TrainStatus, TripDetails},
     footpath::Footpath,
-    location::Location,
+    grid::{distance, GridReference},
+    location::{Activities, Location},
+    metadata::{TimetableMetadata, UpdateType},
+    parse::{date, field, time, FieldError, ParseContext, ParseError, ParseMode},
+    source::{Source, SourceFile},
     stop::{Stop, StopId},
-    trip::{Trip, TripId, TripType},
+    tiploc::{TiplocChange, Tiplocs},
+    trip::{ExtraDetails, Trip, TripId, TripType},
+    update::{Cif, Transaction},
 };
 
-fn find_first_file_with_extension<P: AsRef<Path>>(dir: P, extension: &str) -> Option<PathBuf> {
-    std::fs::read_dir(dir)
-        .ok()?
-        .filter_map(Result::ok)
-        .map(|entry| entry.path())
-        .find(|path| {
-            path.is_file()
-                && path
-                    .extension()
-                    .is_some_and(|ext| ext.to_ascii_lowercase() == extension)
-        })
-}
-
 #[derive(Deserialize, Debug)]
 struct Station {
     #[serde(alias = "3alpha")]
@@ -43,189 +41,550 @@ struct Station {
 }
 
 pub struct Timetable {
+    pub metadata: TimetableMetadata,
     pub stops: Vec<Stop>,
     pub trips: Vec<Trip>,
+    pub associations: Vec<Association>,
     pub footpaths: Vec<Footpath>,
+    /// Every timing point named in the CIF, including those that aren't
+    /// stations
+    pub tiplocs: Tiplocs,
+    /// Records skipped while reading in lenient mode
+    pub warnings: Vec<ParseError>,
 }
 
 impl Timetable {
-    pub fn read<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let msn_path = find_first_file_with_extension(&path, "msn")
+    /// Reads a full timetable from a directory or the zip archive it was
+    /// published as
+    pub fn read<P: AsRef<Path>>(path: P, mode: ParseMode) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut source = Source::open(path)?;
+        // A single file would be read as every file of the timetable
+        if matches!(source, Source::File(_)) {
+            return Err(anyhow!("{}: expected a directory or .zip", path.display()));
+        }
+        let mut ctx = ParseContext::new(mode);
+
+        let msn = source
+            .open_file("msn")?
             .context("Timetable must have .MSN file")?;
-        let mca_path = find_first_file_with_extension(&path, "mca")
+        let stops = read_msn(msn, &mut ctx)?;
+
+        let mca = source
+            .open_file("mca")?
             .context("Timetable must have .MCA file")?;
-        let alf_path = find_first_file_with_extension(&path, "alf")
+        let mut cif = read_mca(mca, &mut ctx)?;
+        if let Some(ztr) = read_ztr(&mut source, &mut ctx)? {
+            cif.extend(ztr);
+        }
+
+        let alf = source
+            .open_file("alf")?
             .context("Timetable must have .ALF file")?;
+        let mut footpaths = read_alf(alf, &mut ctx)?;
+        if let Some(flf) = source.open_file("flf")? {
+            footpaths.extend(read_flf(flf, &mut ctx)?);
+        }
+
+        if cif.metadata.update_type != UpdateType::Full {
+            return Err(anyhow!("Timetable must be a full extract, not an update"));
+        }
 
-        let stops = read_msn(msn_path)?;
-        let trips = read_mca(mca_path)?;
-        let footpaths = read_alf(alf_path)?;
+        let mut tiplocs = Tiplocs::default();
+        tiplocs.apply(cif.tiplocs);
 
         Ok(Self {
+            metadata: cif.metadata,
             stops,
-            trips,
+            trips: cif.trips.into_iter().map(|(_, t)| t).collect(),
+            associations: cif.associations.into_iter().map(|(_, a)| a).collect(),
             footpaths,
+            tiplocs,
+            warnings: ctx.warnings,
         })
     }
 }
 
-fn read_msn(path: impl AsRef<Path>) -> anyhow::Result<Vec<Stop>> {
+/// Furthest in metres an MSN grid reference can be from the coordinates in the
+/// list of stations before we assume it is wrong
+const MAX_GRID_DISCREPANCY: f64 = 2000.0;
+
+/// Grid reference from an MSN station record. Eastings and northings are in
+/// units of 100m, prefixed with 1 and 6 respectively, and missing ones are
+/// blank or zero.
+fn parse_grid_reference(line: &str) -> Option<GridReference> {
+    let easting = line.get(52..57)?.trim().parse::<u32>().ok()?;
+    let northing = line.get(58..63)?.trim().parse::<u32>().ok()?;
+
+    let easting = easting.checked_sub(10000).filter(|&e| e > 0)?;
+    let northing = northing.checked_sub(60000).filter(|&n| n > 0)?;
+
+    Some(GridReference {
+        easting: easting as f64 * 100.0,
+        northing: northing as f64 * 100.0,
+    })
+}
+
+fn read_msn(file: SourceFile, ctx: &mut ParseContext) -> anyhow::Result<Vec<Stop>> {
     let stations_str = include_str!("../../uk-train-stations.json");
-    let stations: Vec<Station> = serde_json::from_str(&stations_str)?;
+    let stations: Vec<Station> = serde_json::from_str(stations_str)?;
     let station_lookup: HashMap<String, Station> =
         stations.into_iter().map(|s| (s.crs.clone(), s)).collect();
 
-    let f = File::open(path)?;
-    let rdr = BufReader::new(f);
-
     let mut stops = vec![];
-    for l in rdr.lines() {
-        let line = l?;
+    // Station names with an alias for each
+    let mut aliases = vec![];
+    ctx.parse_lines(
+        file.reader,
+        &file.name,
+        |line| line.get(0..1),
+        |line| {
+            // Skip comments and header
+            if line.starts_with('/') || line.contains("FILE-SPEC=05") {
+                return Ok(());
+            }
 
-        // Skip comments and header
-        if line.starts_with('/') || line.contains("FILE-SPEC=05") {
-            continue;
-        }
+            // Alias records give a 30 character station name and its alias
+            if line.starts_with('L') {
+                let name = field(line, 5, 35)?.trim().to_owned();
+                let alias = field(line, 36, 66)?.trim().to_owned();
+                aliases.push((name, alias));
+                return Ok(());
+            }
 
-        // Ignore aliases and below
-        if line.starts_with('L') {
-            break;
-        }
+            if !line.starts_with('A') {
+                return Ok(());
+            }
 
-        let name = line[5..31].trim().to_owned();
-        let tiploc = StopId::new(line[36..43].trim());
-        let crs = line[49..52].to_owned();
+            let name = field(line, 5, 35)?.trim().to_owned();
+            let tiploc = StopId::new(field(line, 36, 43)?.trim());
+            let crs = field(line, 49, 52)?.to_owned();
+
+            let change_time = field(line, 63, 65)?.trim();
+            let min_change_time = change_time
+                .parse::<u32>()
+                .map_err(|_| FieldError::at(63, format!("invalid change time '{change_time}'")))?;
+
+            let grid = parse_grid_reference(line).map(|g| g.to_wgs84());
+            let listed = station_lookup
+                .get(&crs)
+                .map(|s| Point::new(s.longitude, s.latitude));
+
+            // Grid references cover every location, but a few are far off, so
+            // trust the list of stations where the two disagree
+            let coord = match (grid, listed) {
+                (Some(grid), Some(listed)) if distance(grid, listed) > MAX_GRID_DISCREPANCY => {
+                    Some(listed)
+                }
+                (Some(grid), _) => Some(grid),
+                (None, listed) => listed,
+            };
+
+            stops.push(Stop::new(tiploc, name, crs, coord, min_change_time));
 
-        let min_change_time = line[64..65].parse::<u32>()?;
+            Ok(())
+        },
+    )?;
 
-        if let Some(station_data) = station_lookup.get(&crs) {
-            let point = geo_types::Point::new(station_data.longitude, station_data.latitude);
-            stops.push(Stop::new(tiploc, name, crs, Some(point), min_change_time));
-        } else {
-            stops.push(Stop::new(tiploc, name, crs, None, min_change_time));
+    // Stations with several TIPLOCs have a station record for each
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, stop) in stops.iter().enumerate() {
+        by_name.entry(&stop.name).or_default().push(idx);
+    }
+
+    let mut stop_aliases: Vec<Vec<String>> = vec![vec![]; stops.len()];
+    for (name, alias) in aliases {
+        for &idx in by_name.get(name.as_str()).into_iter().flatten() {
+            stop_aliases[idx].push(alias.clone());
         }
     }
 
+    for (stop, aliases) in stops.iter_mut().zip(stop_aliases) {
+        stop.aliases = aliases;
+    }
+
     Ok(stops)
 }
 
-fn read_alf<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Footpath>> {
-    let f = File::open(path)?;
-    let rdr = BufReader::new(f);
+fn read_alf(file: SourceFile, ctx: &mut ParseContext) -> anyhow::Result<Vec<Footpath>> {
+    let mut footpaths = vec![];
+    ctx.parse_lines(
+        file.reader,
+        &file.name,
+        |_| None,
+        |line| {
+            footpaths.push(Footpath::parse(line)?);
+            Ok(())
+        },
+    )?;
+
+    Ok(footpaths)
+}
 
+fn read_flf(file: SourceFile, ctx: &mut ParseContext) -> anyhow::Result<Vec<Footpath>> {
     let mut footpaths = vec![];
-    for l in rdr.lines() {
-        let line = l?;
+    ctx.parse_lines(
+        file.reader,
+        &file.name,
+        |_| None,
+        |line| {
+            footpaths.extend(Footpath::parse_fixed_link(line)?);
+            Ok(())
+        },
+    )?;
 
-        let path = Footpath::parse(&line)?;
-        footpaths.push(path);
+    Ok(footpaths)
+}
+
+fn parse_stp_indicator(line: &str) -> Result<TripType, FieldError> {
+    match field(line, 79, 80)? {
+        "P" => Ok(TripType::Permanent),
+        "O" => Ok(TripType::Overlay),
+        "N" => Ok(TripType::New),
+        "C" => Ok(TripType::Cancellation),
+        stp => Err(FieldError::at(79, format!("invalid STP indicator '{stp}'"))),
     }
+}
 
-    Ok(footpaths)
+fn parse_days_run(s: &str) -> [bool; 7] {
+    let mut days_run = [false; 7];
+    s.char_indices().for_each(|(i, d)| {
+        if d == '1' {
+            days_run[i] = true
+        }
+    });
+
+    days_run
 }
 
-fn read_mca<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Trip>> {
-    let f = File::open(path)?;
-    let rdr = BufReader::new(f);
+fn parse_transaction(line: &str) -> Result<Transaction, FieldError> {
+    match field(line, 2, 3)? {
+        "D" => Ok(Transaction::Delete),
+        "R" => Ok(Transaction::Revise),
+        _ => Ok(Transaction::New),
+    }
+}
 
-    let mut trips = vec![];
-    let mut current_trip = None;
+/// Records read so far from a CIF file
+#[derive(Default)]
+struct McaState {
+    metadata: Option<TimetableMetadata>,
+    trips: Vec<(Transaction, Trip)>,
+    associations: Vec<(Transaction, Association)>,
+    tiplocs: Vec<TiplocChange>,
+    current_trip: Option<(Transaction, Trip)>,
+}
 
-    for l in rdr.lines() {
-        let line = l?;
+/// Reads the buses, ships and other non-rail services from a `.ZTR` file, if
+/// there is one. These are in the same format as the `.MCA` file.
+fn read_ztr(source: &mut Source, ctx: &mut ParseContext) -> anyhow::Result<Option<Cif>> {
+    // A single file source is just the .MCA file
+    if matches!(source, Source::File(_)) {
+        return Ok(None);
+    }
 
-        if line.starts_with("HD") || line.starts_with("TI") || line.starts_with("AA") {
-            continue;
-        }
+    let Some(ztr) = source.open_file("ztr")? else {
+        return Ok(None);
+    };
 
-        // Start of a trip
-        if line.starts_with("BS") {
-            let trip_id = TripId::new(line[3..9].to_owned());
-            let start_date = NaiveDate::parse_from_str(&line[9..15], "%y%m%d")?;
-            let end_date = NaiveDate::parse_from_str(&line[15..21], "%y%m%d")?;
-            let trip_type = match line.chars().last() {
-                Some('P') => TripType::Permanent,
-                Some('O') => TripType::Overlay,
-                Some('N') => TripType::New,
-                Some('C') => TripType::Cancellation,
-                _ => panic!("Unexpected character at end of line: {line}"),
-            };
+    let mut cif = read_mca(ztr, ctx)?;
+    for (_, trip) in cif.trips.iter_mut() {
+        trip.mark_non_rail();
+    }
 
-            let mut days_run = [false; 7];
-            line[21..28].char_indices().for_each(|(i, d)| {
-                if d == '1' {
-                    days_run[i] = true
-                }
-            });
-
-            current_trip = Some(Trip::new(
-                trip_id, start_date, end_date, trip_type, days_run,
-            ));
-        } else if line.starts_with("LO") {
-            let tiploc = StopId::new(line[2..9].trim());
-            let departure_time = NaiveTime::parse_from_str(&line[15..19], "%H%M")?;
-
-            let loc = Location::Origin {
-                tiploc,
-                departure_time,
-            };
+    Ok(Some(cif))
+}
 
-            if let Some(current_trip) = current_trip.as_mut() {
-                current_trip.add_location(loc);
-            }
-        } else if line.starts_with("LI") {
-            let activities = &line[42..54];
-            if !(activities.contains("T ")
-                || activities.contains("D ")
-                || activities.contains("U "))
-            {
-                continue;
+/// Parses the TIPLOCs, schedules and associations from a CIF timetable. A schedule
+/// with a malformed record is skipped entirely in lenient mode.
+pub fn read_mca(file: SourceFile, ctx: &mut ParseContext) -> anyhow::Result<Cif> {
+    let mut state = McaState::default();
+
+    ctx.parse_raw_lines(
+        file.reader,
+        &file.name,
+        |line| line.get(0..2),
+        |line| {
+            let result = line.and_then(|line| parse_mca_record(line, &mut state));
+            if result.is_err() {
+                state.current_trip = None;
             }
 
-            let tiploc = StopId::new(line[2..9].trim());
+            result
+        },
+    )?;
 
-            let mut arrival_time = NaiveTime::parse_from_str(&line[25..29], "%H%M")?;
-            let mut departure_time = NaiveTime::parse_from_str(&line[29..33], "%H%M")?;
+    let McaState {
+        metadata,
+        mut trips,
+        associations,
+        tiplocs,
+        current_trip,
+    } = state;
 
-            // If no public time, use scheduled one
-            if &line[25..29] == "0000" {
-                arrival_time = NaiveTime::parse_from_str(&line[10..14], "%H%M")?;
-            }
+    if let Some(trip) = current_trip.filter(|(_, t)| t.locations.is_empty()) {
+        trips.push(trip);
+    }
 
-            // If no public time, use scheduled one
-            if &line[29..33] == "0000" {
-                departure_time = NaiveTime::parse_from_str(&line[15..19], "%H%M")?;
-            }
+    Ok(Cif {
+        metadata: metadata
+            .with_context(|| format!("{} must start with an HD record", file.name))?,
+        trips,
+        associations,
+        tiplocs,
+    })
+}
 
-            let loc = Location::Intermediate {
-                tiploc,
-                arrival_time,
-                departure_time,
-            };
+/// Intermediate and terminating locations have to follow the origin, as
+/// connections are made from each location to the next
+fn check_started(
+    current_trip: &Option<(Transaction, Trip)>,
+    record_type: &str,
+) -> Result<(), FieldError> {
+    match current_trip {
+        Some((_, trip)) if trip.locations.is_empty() => Err(FieldError::new(format!(
+            "{record_type} record before the LO record"
+        ))),
+        _ => Ok(()),
+    }
+}
 
-            if let Some(current_trip) = current_trip.as_mut() {
-                current_trip.add_location(loc);
+fn parse_mca_record(line: &str, state: &mut McaState) -> Result<(), FieldError> {
+    let current_trip = &mut state.current_trip;
+
+    if line.starts_with("HD") {
+        state.metadata = Some(TimetableMetadata::parse(line)?);
+    } else if line.starts_with("TI") || line.starts_with("TA") || line.starts_with("TD") {
+        state.tiplocs.push(TiplocChange::parse(line)?);
+    } else if line.starts_with("AA") {
+        let transaction = parse_transaction(line)?;
+        let start_date = date(line, 15, "%y%m%d")?;
+        // Deletions only identify the association, so have no end date
+        let end_date = match transaction {
+            Transaction::Delete => start_date,
+            _ => date(line, 21, "%y%m%d")?,
+        };
+
+        let category = match field(line, 34, 36)? {
+            "JJ" => Some(AssociationCategory::Join),
+            "VV" => Some(AssociationCategory::Divide),
+            "NP" => Some(AssociationCategory::Next),
+            _ => None,
+        };
+        let date_indicator = match field(line, 36, 37)? {
+            "N" => DateIndicator::Next,
+            "P" => DateIndicator::Previous,
+            _ => DateIndicator::Same,
+        };
+
+        let association = Association {
+            base_uid: TripId::new(field(line, 3, 9)?.to_owned()),
+            assoc_uid: TripId::new(field(line, 9, 15)?.to_owned()),
+            start_date,
+            end_date,
+            days_run: parse_days_run(field(line, 27, 34)?),
+            category,
+            date_indicator,
+            location: StopId::new(field(line, 37, 44)?.trim()),
+            passenger: field(line, 47, 48)? == "P",
+            stp: parse_stp_indicator(line)?,
+        };
+        state.associations.push((transaction, association));
+    // Start of a trip
+    } else if line.starts_with("BS") {
+        // Cancellations and deletions have no locations, so nothing else
+        // ends them
+        if let Some(trip) = current_trip.take() {
+            if trip.1.locations.is_empty() {
+                state.trips.push(trip);
             }
-        } else if line.starts_with("LT") {
-            let tiploc = StopId::new(line[2..9].trim());
-            let arrival_time = NaiveTime::parse_from_str(&line[15..19], "%H%M")?;
+        }
 
-            let loc = Location::Destination {
-                tiploc,
-                arrival_time,
-            };
+        let transaction = parse_transaction(line)?;
+        let trip_id = TripId::new(field(line, 3, 9)?.to_owned());
+        let start_date = date(line, 9, "%y%m%d")?;
+        // Deletions only identify the schedule, so have no end date
+        let end_date = match transaction {
+            Transaction::Delete => start_date,
+            _ => date(line, 15, "%y%m%d")?,
+        };
+        let trip_type = parse_stp_indicator(line)?;
+        let days_run = parse_days_run(field(line, 21, 28)?);
+
+        let status = TrainStatus::from_code(field(line, 29, 30)?.chars().next().unwrap_or(' '));
+        let details = TripDetails::parse(status, line, 30)?;
+
+        *current_trip = Some((
+            transaction,
+            Trip::new(trip_id, start_date, end_date, trip_type, days_run, details),
+        ));
+    } else if line.starts_with("BX") {
+        let extra = ExtraDetails {
+            uic_code: field(line, 6, 11)?.trim().to_owned(),
+            atoc_code: field(line, 11, 13)?.trim().to_owned(),
+            applicable_timetable: field(line, 13, 14)? == "Y",
+        };
+
+        if let Some((_, current_trip)) = current_trip.as_mut() {
+            current_trip.extra = Some(extra);
+        }
+    } else if line.starts_with("CR") {
+        if let Some((_, current_trip)) = current_trip.as_mut() {
+            let details = TripDetails::parse(current_trip.details.status, line, 10)?;
+            current_trip.add_change(details);
+        }
+    } else if line.starts_with("LO") {
+        if current_trip
+            .as_ref()
+            .is_some_and(|(_, t)| !t.locations.is_empty())
+        {
+            return Err(FieldError::new("LO record after the start of the schedule"));
+        }
 
-            if let Some(current_trip) = current_trip.as_mut() {
-                current_trip.add_location(loc);
-            }
+        let tiploc = StopId::new(field(line, 2, 9)?.trim());
+        let departure_time = time(line, 15)?;
+        let activities = Activities::parse(field(line, 29, 41)?);
 
-            if let Some(trip) = current_trip.take() {
-                trips.push(trip);
-            }
+        let loc = Location::Origin {
+            tiploc,
+            departure_time,
+            activities,
+        };
 
-            current_trip = None;
+        if let Some((_, current_trip)) = current_trip.as_mut() {
+            current_trip.add_location(loc);
         }
+    } else if line.starts_with("LI") {
+        check_started(current_trip, "LI")?;
+
+        let activities = Activities::parse(field(line, 42, 54)?);
+        if !activities.is_passenger_stop() {
+            return Ok(());
+        }
+
+        let tiploc = StopId::new(field(line, 2, 9)?.trim());
+
+        let mut arrival_time = time(line, 25)?;
+        let mut departure_time = time(line, 29)?;
+
+        // If no public time, use scheduled one
+        if field(line, 25, 29)? == "0000" {
+            arrival_time = time(line, 10)?;
+        }
+
+        // If no public time, use scheduled one
+        if field(line, 29, 33)? == "0000" {
+            departure_time = time(line, 15)?;
+        }
+
+        let loc = Location::Intermediate {
+            tiploc,
+            arrival_time,
+            departure_time,
+            activities,
+        };
+
+        if let Some((_, current_trip)) = current_trip.as_mut() {
+            current_trip.add_location(loc);
+        }
+    } else if line.starts_with("LT") {
+        check_started(current_trip, "LT")?;
+
+        let tiploc = StopId::new(field(line, 2, 9)?.trim());
+        let arrival_time = time(line, 15)?;
+        let activities = Activities::parse(field(line, 25, 37)?);
+
+        let loc = Location::Destination {
+            tiploc,
+            arrival_time,
+            activities,
+        };
+
+        if let Some((transaction, mut trip)) = current_trip.take() {
+            trip.add_location(loc);
+            state.trips.push((transaction, trip));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The second of three schedules has a BS record with a bad end date
+    const MALFORMED: &str = "malformed.mca";
+
+    #[test]
+    fn strict_mode_fails_at_the_malformed_record() {
+        let mut ctx = ParseContext::new(ParseMode::Strict);
+        let err = fixture::read(MALFORMED, &mut ctx).err().unwrap();
+
+        let err = err.downcast_ref::<ParseError>().unwrap();
+        assert_eq!(err.file, MALFORMED);
+        assert_eq!(err.line, 6);
+        assert_eq!(err.record_type.as_deref(), Some("BS"));
+        assert_eq!(err.column, Some(16));
+        assert_eq!(
+            err.to_string(),
+            "malformed.mca:6: BS record, column 16: invalid date '24XX31'"
+        );
+    }
+
+    #[test]
+    fn lenient_mode_skips_the_whole_schedule() {
+        let mut ctx = ParseContext::new(ParseMode::Lenient);
+        let cif = fixture::read(MALFORMED, &mut ctx).unwrap();
+
+        let uids = cif
+            .trips
+            .iter()
+            .map(|(_, t)| t.id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            uids,
+            vec![
+                TripId::new("C20001".to_owned()),
+                TripId::new("C20003".to_owned())
+            ]
+        );
+
+        assert_eq!(ctx.warnings.len(), 1);
+        assert_eq!(ctx.warnings[0].line, 6);
     }
+}
 
-    Ok(trips)
+/// CIF files in `tests/fixtures` for tests throughout the crate
+#[cfg(test)]
+pub mod fixture {
+    use std::path::PathBuf;
+
+    use crate::timetable::{
+        parse::{ParseContext, ParseMode},
+        read_mca,
+        source::Source,
+        update::Cif,
+    };
+
+    pub fn path(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+            .iter()
+            .collect()
+    }
+
+    /// Reads the fixture `name` in the mode of `ctx`
+    pub fn read(name: &str, ctx: &mut ParseContext) -> anyhow::Result<Cif> {
+        let mut source = Source::open(&path(name))?;
+        let file = source
+            .open_file("mca")?
+            .expect("A single file should always open");
+
+        read_mca(file, ctx)
+    }
+
+    /// Reads the fixture `name`, which has to be well formed
+    pub fn cif(name: &str) -> Cif {
+        read(name, &mut ParseContext::new(ParseMode::Strict)).unwrap()
+    }
 }
@@ -0,0 +1,181 @@
+use std::{fmt, io::BufRead};
+
+use chrono::{NaiveDate, NaiveTime};
+
+/// What to do with a record that can't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail the whole load
+    Strict,
+    /// Skip the record, keeping a warning about it
+    Lenient,
+}
+
+/// A record that couldn't be parsed, and where it is
+#[derive(Debug)]
+pub struct ParseError {
+    /// Name of the file within the timetable
+    pub file: String,
+    /// Line number, counting from 1
+    pub line: usize,
+    /// e.g. `BS` for a CIF basic schedule. Missing for files without record
+    /// types.
+    pub record_type: Option<String>,
+    /// Column the bad field starts at, counting from 1
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+
+        if let Some(record_type) = &self.record_type {
+            write!(f, ": {record_type} record")?;
+        }
+
+        if let Some(column) = self.column {
+            write!(f, ", column {column}")?;
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A problem with one field of a record, before we know which line it is on
+#[derive(Debug)]
+pub struct FieldError {
+    /// Column the field starts at, counting from 1
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    /// Error in the field starting at the 0-based index `start`
+    pub fn at(start: usize, message: impl Into<String>) -> Self {
+        Self {
+            column: Some(start + 1),
+            message: message.into(),
+        }
+    }
+}
+
+/// A value that queries and files give by name
+pub trait Named: Copy + 'static {
+    /// Every value with a name
+    const ALL: &'static [Self];
+
+    fn name(self) -> &'static str;
+
+    /// The value called `s`, ignoring case
+    fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| v.name().eq_ignore_ascii_case(s))
+    }
+}
+
+/// The fixed width field from `start` to `end`, 0-based
+pub fn field(line: &str, start: usize, end: usize) -> Result<&str, FieldError> {
+    line.get(start..end).ok_or_else(|| match line.len() < end {
+        true => FieldError::at(
+            start,
+            format!("line is {} characters, expected at least {end}", line.len()),
+        ),
+        false => FieldError::at(start, "field splits a multibyte character"),
+    })
+}
+
+/// A date in a fixed width field starting at `start`, e.g. `%y%m%d`
+pub fn date(line: &str, start: usize, format: &str) -> Result<NaiveDate, FieldError> {
+    let s = field(line, start, start + 6)?;
+    NaiveDate::parse_from_str(s, format)
+        .map_err(|_| FieldError::at(start, format!("invalid date '{s}'")))
+}
+
+/// A time as `HHMM` in a fixed width field starting at `start`
+pub fn time(line: &str, start: usize) -> Result<NaiveTime, FieldError> {
+    let s = field(line, start, start + 4)?;
+    NaiveTime::parse_from_str(s, "%H%M")
+        .map_err(|_| FieldError::at(start, format!("invalid time '{s}'")))
+}
+
+/// Records which records were skipped while parsing a timetable
+#[derive(Debug)]
+pub struct ParseContext {
+    pub mode: ParseMode,
+    pub warnings: Vec<ParseError>,
+}
+
+impl ParseContext {
+    pub fn new(mode: ParseMode) -> Self {
+        Self {
+            mode,
+            warnings: vec![],
+        }
+    }
+
+    /// Calls `parse_record` on each line of `file`. Errors fail the parse in
+    /// strict mode, and are kept as warnings in lenient mode.
+    pub fn parse_lines<R: BufRead>(
+        &mut self,
+        rdr: R,
+        file: &str,
+        record_type: impl Fn(&str) -> Option<&str>,
+        mut parse_record: impl FnMut(&str) -> Result<(), FieldError>,
+    ) -> anyhow::Result<()> {
+        self.parse_raw_lines(rdr, file, record_type, |line| parse_record(line?))
+    }
+
+    /// Like `parse_lines`, but lines that aren't valid UTF-8 are passed to
+    /// `parse_record` as the error they are reported with, so that it can
+    /// drop the rest of the record they belong to
+    pub fn parse_raw_lines<R: BufRead>(
+        &mut self,
+        mut rdr: R,
+        file: &str,
+        record_type: impl Fn(&str) -> Option<&str>,
+        mut parse_record: impl FnMut(Result<&str, FieldError>) -> Result<(), FieldError>,
+    ) -> anyhow::Result<()> {
+        let mut buf = vec![];
+        for idx in 0.. {
+            buf.clear();
+            if rdr.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            let bytes = buf.strip_suffix(b"\n").unwrap_or(&buf);
+            let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+
+            let line = std::str::from_utf8(bytes)
+                .map_err(|e| FieldError::at(e.valid_up_to(), "line is not valid UTF-8"));
+
+            if let Err(e) = parse_record(line) {
+                let line = String::from_utf8_lossy(bytes);
+                let error = ParseError {
+                    file: file.to_owned(),
+                    line: idx + 1,
+                    record_type: record_type(&line).map(str::to_owned),
+                    column: e.column,
+                    message: e.message,
+                };
+
+                match self.mode {
+                    ParseMode::Strict => return Err(error.into()),
+                    ParseMode::Lenient => self.warnings.push(error),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+/// Where the files of a timetable are read from: a directory of loose files,
+/// a zip archive as published by National Rail, or a single update file. Any
+/// of the files may also be gzipped.
+pub enum Source {
+    Directory(PathBuf),
+    Zip(Box<ZipArchive<BufReader<File>>>),
+    File(PathBuf),
+}
+
+/// A file within a timetable, ready to read
+pub struct SourceFile<'a> {
+    pub name: String,
+    pub reader: Box<dyn BufRead + 'a>,
+}
+
+/// Whether `name` is a file with `extension`, possibly gzipped
+fn has_extension(name: &str, extension: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+
+    Path::new(name)
+        .extension()
+        .is_some_and(|ext| ext == extension)
+}
+
+fn is_gzipped(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".gz")
+}
+
+fn decompress<'a, R: BufRead + 'a>(rdr: R, gzipped: bool) -> Box<dyn BufRead + 'a> {
+    if gzipped {
+        Box::new(BufReader::new(GzDecoder::new(rdr)))
+    } else {
+        Box::new(rdr)
+    }
+}
+
+impl Source {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Err(anyhow!("{}: not found", path.display()));
+        }
+
+        if path.is_dir() {
+            return Ok(Source::Directory(path.to_path_buf()));
+        }
+
+        let is_zip = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+
+        if is_zip {
+            let f =
+                File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let archive = ZipArchive::new(BufReader::new(f))
+                .with_context(|| format!("Failed to read archive {}", path.display()))?;
+
+            Ok(Source::Zip(Box::new(archive)))
+        } else {
+            Ok(Source::File(path.to_path_buf()))
+        }
+    }
+
+    /// Streams the first file with `extension`, e.g. `mca`, decompressing it
+    /// if it is gzipped. A single file source is returned whatever its
+    /// extension.
+    pub fn open_file(&mut self, extension: &str) -> anyhow::Result<Option<SourceFile<'_>>> {
+        match self {
+            Source::Directory(dir) => {
+                let path = std::fs::read_dir(dir)?
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .find(|path| {
+                        path.is_file()
+                            && path
+                                .file_name()
+                                .and_then(|name| name.to_str())
+                                .is_some_and(|name| has_extension(name, extension))
+                    });
+
+                path.map(|path| Self::open_path(&path)).transpose()
+            }
+            Source::Zip(archive) => {
+                let name = archive
+                    .file_names()
+                    .find(|name| has_extension(name, extension))
+                    .map(str::to_owned);
+
+                let Some(name) = name else {
+                    return Ok(None);
+                };
+
+                let member = archive.by_name(&name)?;
+                let reader = decompress(BufReader::new(member), is_gzipped(&name));
+
+                Ok(Some(SourceFile { name, reader }))
+            }
+            Source::File(path) => Self::open_path(path).map(Some),
+        }
+    }
+
+    fn open_path(path: &Path) -> anyhow::Result<SourceFile<'static>> {
+        let f = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let reader = decompress(BufReader::new(f), is_gzipped(&name));
+
+        Ok(SourceFile { name, reader })
+    }
+}
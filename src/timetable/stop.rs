@@ -1,13 +1,17 @@
 use geo_types::Point;
 use serde::Serialize;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub struct StopId(String);
 
 impl StopId {
     pub fn new(str: &str) -> Self {
         Self(str.to_owned())
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
 #[allow(unused)]
@@ -16,6 +20,8 @@ pub struct Stop {
     pub tiploc: StopId,
     pub name: String,
     pub crs: String,
+    /// Other names the station is known by, e.g. `KINGS X`
+    pub aliases: Vec<String>,
     pub min_change_time: u32,
     pub coord: Option<Point>,
 }
@@ -32,6 +38,7 @@ impl Stop {
             tiploc,
             name,
             crs,
+            aliases: vec![],
             coord,
             min_change_time,
         }
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::timetable::{
+    parse::{field, FieldError},
+    stop::StopId,
+};
+
+/// A timing point from the CIF, including junctions and other places trains
+/// only pass
+#[derive(Debug, Clone, Serialize)]
+pub struct TiplocDetails {
+    pub tiploc: StopId,
+    /// National Location Code
+    pub nlc: String,
+    /// Description in the Train Planning System, e.g. `CLAPHAM JUNCTION`
+    pub description: String,
+    /// Station number used by TOPS, if it has one
+    pub stanox: Option<String>,
+    /// CRS code, if it is a station
+    pub crs: Option<String>,
+}
+
+impl TiplocDetails {
+    /// Details from a TI or TA record
+    fn parse(line: &str) -> Result<Self, FieldError> {
+        let optional = |s: &str| Some(s.trim().to_owned()).filter(|s| !s.is_empty());
+
+        Ok(Self {
+            tiploc: StopId::new(field(line, 2, 9)?.trim()),
+            nlc: field(line, 11, 17)?.trim().to_owned(),
+            description: field(line, 18, 44)?.trim().to_owned(),
+            stanox: optional(field(line, 44, 49)?).filter(|s| s != "00000"),
+            crs: optional(field(line, 53, 56)?),
+        })
+    }
+}
+
+/// A change to the TIPLOC dictionary from a TI, TA or TD record
+#[derive(Debug)]
+pub enum TiplocChange {
+    Insert(TiplocDetails),
+    /// Replaces the details of `tiploc`, which may be renamed
+    Amend {
+        tiploc: StopId,
+        details: TiplocDetails,
+    },
+    Delete(StopId),
+}
+
+impl TiplocChange {
+    pub fn parse(line: &str) -> Result<Self, FieldError> {
+        match field(line, 0, 2)? {
+            "TI" => Ok(Self::Insert(TiplocDetails::parse(line)?)),
+            "TA" => {
+                let mut details = TiplocDetails::parse(line)?;
+                let tiploc = details.tiploc.clone();
+
+                let new_tiploc = line.get(72..79).unwrap_or_default().trim();
+                if !new_tiploc.is_empty() {
+                    details.tiploc = StopId::new(new_tiploc);
+                }
+
+                Ok(Self::Amend { tiploc, details })
+            }
+            "TD" => Ok(Self::Delete(StopId::new(field(line, 2, 9)?.trim()))),
+            record_type => Err(FieldError::at(
+                0,
+                format!("'{record_type}' is not a TIPLOC record"),
+            )),
+        }
+    }
+}
+
+/// Every timing point in the timetable, by TIPLOC
+#[derive(Debug, Default, Clone)]
+pub struct Tiplocs(HashMap<StopId, TiplocDetails>);
+
+impl Tiplocs {
+    pub fn apply(&mut self, changes: Vec<TiplocChange>) {
+        for change in changes {
+            match change {
+                TiplocChange::Insert(details) => {
+                    self.0.insert(details.tiploc.clone(), details);
+                }
+                TiplocChange::Amend { tiploc, details } => {
+                    self.0.remove(&tiploc);
+                    self.0.insert(details.tiploc.clone(), details);
+                }
+                TiplocChange::Delete(tiploc) => {
+                    self.0.remove(&tiploc);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, tiploc: &StopId) -> Option<&TiplocDetails> {
+        self.0.get(tiploc)
+    }
+}
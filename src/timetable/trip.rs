@@ -1,8 +1,13 @@
 use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
 
-use crate::timetable::location::Location;
+use crate::timetable::{
+    details::{TrainStatus, TripDetails},
+    location::Location,
+    parse::Named,
+};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub enum TripType {
     Permanent,
     New,
@@ -10,7 +15,51 @@ pub enum TripType {
     Cancellation,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+impl TripType {
+    /// Precedence when schedules for the same train overlap: cancellations
+    /// beat overlays and new schedules, which beat permanent ones
+    pub fn priority(&self) -> u8 {
+        match self {
+            TripType::Permanent => 0,
+            TripType::New | TripType::Overlay => 1,
+            TripType::Cancellation => 2,
+        }
+    }
+}
+
+/// How a trip is run, as `.ZTR` files add buses and ships to the rail
+/// timetable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TripMode {
+    Train,
+    Bus,
+    Ship,
+}
+
+impl TripMode {
+    fn from_details(details: &TripDetails) -> Self {
+        match (details.status, details.category.as_str()) {
+            (TrainStatus::Ship, _) | (_, "SS") => TripMode::Ship,
+            (TrainStatus::Bus, _) | (_, "BR" | "BS") => TripMode::Bus,
+            _ => TripMode::Train,
+        }
+    }
+}
+
+impl Named for TripMode {
+    const ALL: &'static [Self] = &[TripMode::Train, TripMode::Bus, TripMode::Ship];
+
+    fn name(self) -> &'static str {
+        match self {
+            TripMode::Train => "train",
+            TripMode::Bus => "bus",
+            TripMode::Ship => "ship",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize)]
 pub struct TripId(String);
 
 impl TripId {
@@ -19,13 +68,36 @@ impl TripId {
     }
 }
 
-#[derive(Debug)]
+/// Extra schedule details from a BX record
+#[derive(Debug, Clone)]
+pub struct ExtraDetails {
+    /// Code of international services, blank for others
+    pub uic_code: String,
+    /// Two letter code of the train operating company
+    pub atoc_code: String,
+    /// Whether the schedule is subject to performance monitoring
+    pub applicable_timetable: bool,
+}
+
+/// New service attributes from a CR record, taking effect from a location
+#[derive(Debug, Clone)]
+pub struct EnRouteChange {
+    /// Index into `Trip::locations` of the first location the change applies to
+    pub location_idx: usize,
+    pub details: TripDetails,
+}
+
+#[derive(Debug, Clone)]
 pub struct Trip {
     pub id: TripId,
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
     pub trip_type: TripType,
     pub days_run: [bool; 7],
+    pub details: TripDetails,
+    pub mode: TripMode,
+    pub extra: Option<ExtraDetails>,
+    pub changes: Vec<EnRouteChange>,
     pub locations: Vec<Location>,
 }
 
@@ -36,6 +108,7 @@ impl Trip {
         end_date: NaiveDate,
         trip_type: TripType,
         days_run: [bool; 7],
+        details: TripDetails,
     ) -> Self {
         Self {
             id,
@@ -43,6 +116,10 @@ impl Trip {
             end_date,
             trip_type,
             days_run,
+            mode: TripMode::from_details(&details),
+            details,
+            extra: None,
+            changes: vec![],
             locations: vec![],
         }
     }
@@ -53,7 +130,23 @@ impl Trip {
             && self.days_run[date.weekday().num_days_from_monday() as usize]
     }
 
+    /// Marks a trip from a `.ZTR` file, which only has non-rail services, as
+    /// a bus unless it is a ship
+    pub fn mark_non_rail(&mut self) {
+        if self.mode == TripMode::Train {
+            self.mode = TripMode::Bus;
+        }
+    }
+
     pub fn add_location(&mut self, loc: Location) {
         self.locations.push(loc);
     }
+
+    /// Records a change of attributes applying from the next location added
+    pub fn add_change(&mut self, details: TripDetails) {
+        self.changes.push(EnRouteChange {
+            location_idx: self.locations.len(),
+            details,
+        });
+    }
 }
@@ -0,0 +1,231 @@
+use std::{collections::HashMap, hash::Hash, path::Path};
+
+use anyhow::{anyhow, Context};
+use chrono::NaiveDate;
+
+use crate::timetable::{
+    association::Association,
+    metadata::{TimetableMetadata, UpdateType},
+    parse::{ParseContext, ParseMode},
+    read_mca, read_ztr,
+    source::Source,
+    stop::StopId,
+    tiploc::TiplocChange,
+    trip::{Trip, TripId, TripType},
+    Timetable,
+};
+
+/// What a schedule or association record does to the timetable, from its
+/// transaction type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transaction {
+    New,
+    Delete,
+    Revise,
+}
+
+/// TIPLOCs, schedules and associations read from a CIF file
+pub struct Cif {
+    pub metadata: TimetableMetadata,
+    pub trips: Vec<(Transaction, Trip)>,
+    pub associations: Vec<(Transaction, Association)>,
+    /// TIPLOC inserts, amendments and deletions, in order
+    pub tiplocs: Vec<TiplocChange>,
+}
+
+impl Cif {
+    /// Adds the records from another file of the same extract, e.g. its
+    /// `.ZTR` file, keeping this file's metadata
+    pub fn extend(&mut self, other: Cif) {
+        self.trips.extend(other.trips);
+        self.associations.extend(other.associations);
+        self.tiplocs.extend(other.tiplocs);
+    }
+}
+
+/// Identifies a schedule across update files
+fn trip_key(t: &Trip) -> (TripId, NaiveDate, TripType) {
+    (t.id.clone(), t.start_date, t.trip_type)
+}
+
+/// Identifies an association across update files
+fn association_key(a: &Association) -> (TripId, TripId, NaiveDate, StopId, TripType) {
+    (
+        a.base_uid.clone(),
+        a.assoc_uid.clone(),
+        a.start_date,
+        a.location.clone(),
+        a.stp,
+    )
+}
+
+/// Applies `changes` in order to `records`, matching existing records by
+/// `key`
+fn apply<T, K: Eq + Hash>(
+    records: &mut Vec<T>,
+    changes: Vec<(Transaction, T)>,
+    key: impl Fn(&T) -> K,
+) {
+    let mut index: HashMap<K, usize> = records
+        .iter()
+        .enumerate()
+        .map(|(idx, r)| (key(r), idx))
+        .collect();
+    let mut slots: Vec<Option<T>> = records.drain(..).map(Some).collect();
+
+    for (transaction, record) in changes {
+        let k = key(&record);
+
+        match (transaction, index.get(&k)) {
+            (Transaction::Delete, Some(&idx)) => {
+                slots[idx] = None;
+                index.remove(&k);
+            }
+            (Transaction::Delete, None) => {}
+            (_, Some(&idx)) => slots[idx] = Some(record),
+            (_, None) => {
+                index.insert(k, slots.len());
+                slots.push(Some(record));
+            }
+        }
+    }
+
+    *records = slots.into_iter().flatten().collect();
+}
+
+impl Timetable {
+    /// Applies a CIF update file on top of the timetable. `path` is either
+    /// the .MCA file or a directory or zip archive containing one, along with
+    /// any .ZTR update for non-rail services.
+    ///
+    /// Schedules are matched by UID, start date and STP indicator, and
+    /// associations by both UIDs, start date, location and STP indicator.
+    /// Updates have to be applied in the order they were published, so one
+    /// that doesn't follow the last file applied is rejected.
+    pub fn apply_update<P: AsRef<Path>>(&mut self, path: P, mode: ParseMode) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut source = Source::open(path)?;
+        let mca = source
+            .open_file("mca")?
+            .context("Update must have .MCA file")?;
+
+        let mut ctx = ParseContext::new(mode);
+        let mut cif = read_mca(mca, &mut ctx)?;
+        if let Some(ztr) = read_ztr(&mut source, &mut ctx)? {
+            cif.extend(ztr);
+        }
+
+        if cif.metadata.update_type != UpdateType::Update {
+            return Err(anyhow!(
+                "{} is a full extract, not an update",
+                path.display()
+            ));
+        }
+
+        // Each update names the file it follows, and applying one out of order
+        // would leave the timetable wrong
+        if cif.metadata.last_file_reference.as_ref() != Some(&self.metadata.file_reference) {
+            return Err(anyhow!(
+                "{} follows {}, but the timetable is at {}",
+                path.display(),
+                cif.metadata
+                    .last_file_reference
+                    .as_deref()
+                    .unwrap_or("no file"),
+                self.metadata.file_reference
+            ));
+        }
+
+        self.warnings.extend(ctx.warnings);
+        apply(&mut self.trips, cif.trips, trip_key);
+        apply(&mut self.associations, cif.associations, association_key);
+        self.tiplocs.apply(cif.tiplocs);
+
+        // The update identifies the feed version, while the dates covered
+        // still include the full extract's
+        self.metadata = TimetableMetadata {
+            valid_from: self.metadata.valid_from.min(cif.metadata.valid_from),
+            valid_until: self.metadata.valid_until.max(cif.metadata.valid_until),
+            ..cif.metadata
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::{fixture, tiploc::Tiplocs};
+
+    fn timetable() -> Timetable {
+        let cif = fixture::cif("update_base.mca");
+
+        Timetable {
+            metadata: cif.metadata,
+            stops: vec![],
+            trips: cif.trips.into_iter().map(|(_, t)| t).collect(),
+            associations: vec![],
+            footpaths: vec![],
+            tiplocs: Tiplocs::default(),
+            warnings: vec![],
+        }
+    }
+
+    /// Departure time of each schedule, by UID, start date and STP indicator
+    fn schedules(timetable: &Timetable) -> HashMap<(TripId, NaiveDate, TripType), String> {
+        timetable
+            .trips
+            .iter()
+            .map(|t| {
+                let departure_time = t.locations[0].departure_time().unwrap();
+                (trip_key(t), departure_time.format("%H:%M").to_string())
+            })
+            .collect()
+    }
+
+    fn key(uid: &str, start_date: &str, trip_type: TripType) -> (TripId, NaiveDate, TripType) {
+        let start_date = NaiveDate::parse_from_str(start_date, "%Y-%m-%d").unwrap();
+        (TripId::new(uid.to_owned()), start_date, trip_type)
+    }
+
+    #[test]
+    fn update_adds_deletes_and_revises_schedules() {
+        let mut timetable = timetable();
+        timetable
+            .apply_update(fixture::path("update.mca"), ParseMode::Strict)
+            .unwrap();
+
+        // C10002 is deleted, as is only the overlay of C10003
+        let expected = HashMap::from([
+            (
+                key("C10001", "2024-01-01", TripType::Permanent),
+                "08:05".to_owned(),
+            ),
+            (
+                key("C10003", "2024-01-01", TripType::Permanent),
+                "10:00".to_owned(),
+            ),
+            (
+                key("C10004", "2024-01-01", TripType::Permanent),
+                "11:00".to_owned(),
+            ),
+        ]);
+        assert_eq!(schedules(&timetable), expected);
+        assert_eq!(timetable.metadata.file_reference, "DFROC1B");
+    }
+
+    #[test]
+    fn update_must_follow_the_last_file_applied() {
+        let mut timetable = timetable();
+        timetable
+            .apply_update(fixture::path("update.mca"), ParseMode::Strict)
+            .unwrap();
+
+        let err = timetable
+            .apply_update(fixture::path("update.mca"), ParseMode::Strict)
+            .unwrap_err();
+        assert!(err.to_string().contains("follows DFROC1A"));
+        assert_eq!(timetable.metadata.file_reference, "DFROC1B");
+    }
+}